@@ -0,0 +1,426 @@
+//! Checks of script which report all found problems at once: parse errors
+//! of every item like unknown lists and fields, empty commands, programs
+//! missing in `PATH`, duplicate ids and missing `cwd` directories
+//!
+//! # Example
+//!```
+//!use automatic::check::Check;
+//!use automatic::script::Script;
+//!
+//!let script = Script::from_str("---\nSilent:\n  items:\n    - shell: \"true\"\n      cdw: /tmp\n    - shell: \"true\"\n      timeout: x\n");
+//!
+//!let check = Check::new(&script);
+//!assert!(!check.is_ok());
+//!assert_eq!(check.diagnostics().len(), 2);
+//!assert_eq!(check.diagnostics()[0].location(), Some((5, 7)));
+//!assert!(check.diagnostics()[0]
+//!    .message()
+//!    .starts_with("Silent.items[0]: unknown field `cdw`, expected one of"));
+//!```
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use serde::de::IgnoredAny;
+
+use crate::error::Error;
+use crate::plan::Visitor;
+use crate::runitem::{Defaults, RunItem};
+use crate::script::{List, Script};
+
+/// Problem of script, location is `(line, column)` in script file
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    location: Option<(usize, usize)>,
+    message: String,
+}
+
+impl Diagnostic {
+    pub fn new<S: Into<String>>(location: Option<(usize, usize)>, message: S) -> Self {
+        Self {
+            location,
+            message: message.into(),
+        }
+    }
+
+    pub fn location(&self) -> Option<(usize, usize)> {
+        self.location
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.location {
+            Some((line, column)) => write!(f, "{}:{}: {}", line, column, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+/// Problems found in script
+#[derive(Debug, Clone, PartialEq)]
+pub struct Check {
+    path: PathBuf,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Check {
+    /// Check script and run lists of all its targets
+    pub fn new(script: &Script) -> Self {
+        let mut check = Self {
+            path: script.path().to_path_buf(),
+            diagnostics: vec![],
+        };
+
+        let source = match script.read() {
+            Ok(source) => source,
+            Err(e) => {
+                check.diagnostics.push(Diagnostic::new(None, e.to_string()));
+                return check;
+            }
+        };
+        // Parser stops at the first error, so script is read as document
+        // tree where every item is parsed on its own
+        let errors = match script
+            .decode::<IgnoredAny>(&source)
+            .and_then(|_| script.parse_errors(&source))
+        {
+            Ok(errors) => errors,
+            Err(e) => {
+                check.diagnostics.push(parse_diagnostic(e));
+                return check;
+            }
+        };
+        for error in errors {
+            check.report(located(&error));
+        }
+        check
+            .diagnostics
+            .sort_by_key(|diagnostic| diagnostic.location);
+        if !check.is_ok() {
+            return check;
+        }
+
+        let targets = match script.targets() {
+            Ok(targets) => targets,
+            Err(e) => {
+                check.report(parse_diagnostic(e));
+                return check;
+            }
+        };
+
+        let targets: Vec<Option<String>> = match targets.is_empty() {
+            true => vec![None],
+            false => targets
+                .iter()
+                .map(|(name, _)| Some(name.to_string()))
+                .collect(),
+        };
+        for target in targets {
+            let (script, prefix) = match &target {
+                Some(name) => (
+                    script.clone().with_target(name.clone()),
+                    format!("target `{}`: ", name),
+                ),
+                None => (script.clone(), String::new()),
+            };
+            let lists = script
+                .parse_unvalidated()
+                .and_then(|list| Ok((list, script.cleanup_unvalidated()?)));
+            let (list, cleanup) = match lists {
+                Ok(lists) => lists,
+                Err(e) => {
+                    check
+                        .diagnostics
+                        .push(Diagnostic::new(None, format!("{}{}", prefix, e)));
+                    continue;
+                }
+            };
+
+            let mut items = Items {
+                prefix,
+                diagnostics: vec![],
+            };
+            list.accept(&mut items);
+            cleanup.accept(&mut items);
+            check.diagnostics.extend(items.diagnostics);
+        }
+        check
+    }
+
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    /// No problems are found
+    pub fn is_ok(&self) -> bool {
+        self.diagnostics.is_empty()
+    }
+
+    /// Add diagnostic which isn't reported yet
+    fn report(&mut self, diagnostic: Diagnostic) {
+        if !self.diagnostics.contains(&diagnostic) {
+            self.diagnostics.push(diagnostic);
+        }
+    }
+}
+
+impl fmt::Display for Check {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for diagnostic in self.diagnostics.iter() {
+            match diagnostic.location {
+                Some(_) => writeln!(f, "{}:{}", self.path.display(), diagnostic)?,
+                None => writeln!(f, "{}: {}", self.path.display(), diagnostic)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Diagnostic of parse error without location appended to message by parser
+fn parse_diagnostic(e: Error) -> Diagnostic {
+    match e {
+        Error::Parse {
+            location: Some((line, column)),
+            message,
+            ..
+        } => {
+            let suffix = format!(" at line {} column {}", line, column);
+            let message = message.strip_suffix(&suffix).unwrap_or(&message);
+            Diagnostic::new(Some((line, column)), message)
+        }
+        Error::Parse { message, .. } => Diagnostic::new(None, message),
+        e => Diagnostic::new(None, e.to_string()),
+    }
+}
+
+/// Diagnostic of parse error which message ends with its location
+fn located(message: &str) -> Diagnostic {
+    let location = message.rfind(" at line ").and_then(|at| {
+        let (line, column) = message[at + " at line ".len()..].split_once(" column ")?;
+        Some((at, (line.parse().ok()?, column.parse().ok()?)))
+    });
+    match location {
+        Some((at, location)) => Diagnostic::new(Some(location), &message[..at]),
+        None => Diagnostic::new(None, message),
+    }
+}
+
+/// Check of run items with applied defaults of their lists
+struct Items {
+    prefix: String,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Items {
+    fn report(&mut self, message: String) {
+        self.diagnostics
+            .push(Diagnostic::new(None, format!("{}{}", self.prefix, message)));
+    }
+}
+
+impl Visitor for Items {
+    fn enter_list(&mut self, list: &List, _defaults: &Defaults) {
+        let graph = match list {
+            List::Graph(graph) => graph,
+            _ => return,
+        };
+        let mut ids = BTreeMap::new();
+        for item in graph.items() {
+            if let Some(id) = item.id() {
+                *ids.entry(id).or_insert(0) += 1;
+            }
+        }
+        for (id, count) in ids {
+            if count > 1 {
+                self.report(format!("duplicate item id `{}` in graph list", id));
+            }
+        }
+        match graph.order() {
+            Ok(_) | Err(Error::DuplicateId(_)) => (),
+            Err(e) => self.report(e.to_string()),
+        }
+    }
+
+    fn visit_item(&mut self, item: &RunItem) {
+        if item.name().is_empty() {
+            self.report("item has empty command".to_string());
+            return;
+        }
+        if let Some(cwd) = item.cwd() {
+            if !cwd.is_dir() {
+                self.report(format!(
+                    "cwd `{}` of `{}` doesn't exist",
+                    cwd.display(),
+                    item
+                ));
+                return;
+            }
+        }
+        if !program_exists(item) {
+            self.report(format!(
+                "program `{}` of `{}` is not found",
+                item.program(),
+                item
+            ));
+        }
+    }
+}
+
+/// Program of item is found in `PATH` of item or by its path
+fn program_exists(item: &RunItem) -> bool {
+    let program = Path::new(item.program());
+    if program.components().count() > 1 {
+        return match item.cwd() {
+            Some(cwd) => cwd.join(program).is_file(),
+            None => program.is_file(),
+        };
+    }
+    let path = match item.env().get("PATH") {
+        Some(path) => Some(path.into()),
+        None => env::var_os("PATH"),
+    };
+    path.is_some_and(|path| env::split_paths(&path).any(|dir| dir.join(program).is_file()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Diagnostics of script as strings
+    fn messages(check: &Check) -> Vec<String> {
+        check
+            .diagnostics()
+            .iter()
+            .map(|diagnostic| diagnostic.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn fields() {
+        let check = Check::new(&Script::from_str("---\nvars: {}\nSilnt:\n  items: []\n"));
+        assert_eq!(
+            check.to_string(),
+            "-:3:1: unknown list `Silnt`, expected one of \
+             Interactive, Promiscuous, Silent, Parallel, Pipeline, Graph\n"
+        );
+
+        let source = r#"{
+  "Parallel": {
+    "policy": "fail_fast",
+    "items": [
+      {"name": "true", "args": [], "retry": {"attempts": 2, "dely": 1}},
+      {"Pipeline": {"items": [{"args": []}], "defaults": {"cdw": "/"}}}
+    ]
+  }
+}
+"#;
+        let check = Check::new(&Script::from_str(source).with_format(crate::format::Format::Json));
+        assert_eq!(
+            messages(&check),
+            vec![
+                "3:15: Parallel.policy: unknown variant `fail_fast`, expected `WaitAll` or \
+                 `FailFast`",
+                "5:61: Parallel.items[0].retry: unknown field `dely`, expected one of \
+                 `attempts`, `delay`, `backoff`, `codes`",
+                "6:31: Parallel.items[1].Pipeline.items[0]: missing field `name`",
+                "6:59: Parallel.items[1].Pipeline.defaults: unknown field `cdw`, expected one of \
+                 `env`, `env_clear`, `env_remove`, \
+                 `cwd`, `timeout`, `process_group`, `shell`",
+            ]
+        );
+
+        // Missing field is reported at its item, not at the first item with
+        // the same fields
+        let check = Check::new(&Script::from_str(
+            "---\ncleanup:\n  - name: \"true\"\n    arg: []\nSilent:\n  items:\n    - name: \"true\"\n      args: []\n    - name: \"true\"\n",
+        ));
+        assert_eq!(
+            messages(&check),
+            vec![
+                "4:5: cleanup[0]: unknown field `arg`, expected one of `id`, `needs`, `name`, \
+                 `args`, `shell`, `env`, `env_clear`, `env_remove`, `cwd`, `timeout`, \
+                 `process_group`, `retry`, `success_codes`, `ignore_failure`, `when`, `unless`, \
+                 `when_cmd`, `include`, `target`, `Interactive`, `Promiscuous`, `Silent`, \
+                 `Parallel`, `Pipeline`, `Graph`",
+                "9:11: Silent.items[1]: missing field `args`",
+            ]
+        );
+
+        // Every item is parsed once and its error doesn't stop parse of
+        // the next items
+        let source = "---\nSilent:\n  items:\n".to_string()
+            + &"    - shell: \"true\"\n      cdw: /\n".repeat(3000);
+        let check = Check::new(&Script::from_str(&source));
+        assert_eq!(check.diagnostics().len(), 3000);
+        assert_eq!(check.diagnostics()[2999].location(), Some((6003, 7)));
+
+        let check = Check::new(&Script::new(PathBuf::from(
+            "tests/test_script_for_run_cleanup.yaml",
+        )));
+        assert!(check.is_ok());
+    }
+
+    #[test]
+    fn items() {
+        let source = "---
+Silent:
+  items:
+    - name: \"\"
+      args: []
+    - name: \"no-such-program-for-automatic\"
+      args: []
+    - shell: \"true\"
+      cwd: no/such/dir
+    - Graph:
+        items:
+          - name: \"true\"
+            args: []
+            id: a
+          - name: \"./Cargo.toml\"
+            args: []
+            id: a
+    - Graph:
+        items:
+          - name: \"true\"
+            args: []
+            needs: [b]
+";
+        let check = Check::new(&Script::from_str(source));
+        let messages: Vec<&str> = check
+            .diagnostics()
+            .iter()
+            .map(Diagnostic::message)
+            .collect();
+        assert_eq!(
+            messages,
+            vec![
+                "item has empty command",
+                "program `no-such-program-for-automatic` of `no-such-program-for-automatic` is not found",
+                "cwd `no/such/dir` of `true` doesn't exist",
+                "duplicate item id `a` in graph list",
+                "`true` needs unknown item id `b`",
+            ]
+        );
+
+        let check = Check::new(&Script::new(PathBuf::from(
+            "tests/test_script_for_run_targets.yaml",
+        )));
+        assert_eq!(
+            check.to_string(),
+            "tests/test_script_for_run_targets.yaml: target `build`: \
+             undefined variable `BUILD_ARGS`\n"
+        );
+
+        assert!(Check::new(&Script::new(PathBuf::from(
+            "tests/test_script_for_run_graph.yaml"
+        )))
+        .is_ok());
+    }
+}
@@ -0,0 +1,174 @@
+//! Conditions of item run checked before spawn, all set checks of condition
+//! must pass
+//!
+//! # Example
+//!```
+//!use automatic::condition::Condition;
+//!use automatic::runitem::RunItem;
+//!use automatic::run::Run;
+//!
+//!let item = RunItem::new("false".to_string(), vec![])
+//!    .with_when(Condition::new().with_os("no such os"));
+//!
+//!let status = item.run();
+//!assert!(status.status().expect("failed to execute process").success());
+//!assert!(status.report().items[0].skipped);
+//!```
+
+use std::collections::BTreeMap;
+use std::env;
+use std::path::PathBuf;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::error::Error;
+use crate::runitem::RunItem;
+use crate::vars::Vars;
+
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize, JsonSchema, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Condition {
+    /// Path exists, relative path is resolved against cwd of item
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    exists: Option<PathBuf>,
+    /// Environment variable of item is set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    env: Option<String>,
+    /// Environment variables of item have these values
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    env_equals: BTreeMap<String, String>,
+    /// Operating system like `linux` or `macos`, see [`std::env::consts::OS`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    os: Option<String>,
+    /// Architecture like `x86_64` or `aarch64`, see [`std::env::consts::ARCH`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    arch: Option<String>,
+}
+
+impl Condition {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_exists<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.exists = Some(path.into());
+        self
+    }
+
+    pub fn with_env<K: Into<String>>(mut self, key: K) -> Self {
+        self.env = Some(key.into());
+        self
+    }
+
+    pub fn with_env_equals<K: Into<String>, V: Into<String>>(mut self, key: K, val: V) -> Self {
+        self.env_equals.insert(key.into(), val.into());
+        self
+    }
+
+    pub fn with_os<S: Into<String>>(mut self, os: S) -> Self {
+        self.os = Some(os.into());
+        self
+    }
+
+    pub fn with_arch<S: Into<String>>(mut self, arch: S) -> Self {
+        self.arch = Some(arch.into());
+        self
+    }
+
+    /// Check condition for item with applied defaults of its lists
+    pub fn holds(&self, item: &RunItem) -> bool {
+        if let Some(path) = &self.exists {
+            let path = match item.cwd() {
+                Some(cwd) => cwd.join(path),
+                None => path.clone(),
+            };
+            if !path.exists() {
+                return false;
+            }
+        }
+        if let Some(key) = &self.env {
+            if env_var(item, key).is_none() {
+                return false;
+            }
+        }
+        for (key, val) in self.env_equals.iter() {
+            if env_var(item, key).as_ref() != Some(val) {
+                return false;
+            }
+        }
+        if self.os.as_ref().is_some_and(|os| os != env::consts::OS) {
+            return false;
+        }
+        if self
+            .arch
+            .as_ref()
+            .is_some_and(|arch| arch != env::consts::ARCH)
+        {
+            return false;
+        }
+        true
+    }
+
+    pub(crate) fn interpolate(&mut self, vars: &Vars) -> Result<(), Error> {
+        if let Some(path) = self.exists.as_mut() {
+            vars.interpolate_path(path)?;
+        }
+        vars.interpolate_env(&mut self.env_equals)
+    }
+}
+
+/// Value of environment variable which item gets
+fn env_var(item: &RunItem, key: &str) -> Option<String> {
+    if let Some(val) = item.env().get(key) {
+        return Some(val.clone());
+    }
+    if item.env_clear() || item.env_remove().iter().any(|removed| removed == key) {
+        return None;
+    }
+    env::var(key).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn holds() {
+        let item = RunItem::new("true".to_string(), vec![])
+            .with_cwd("tests")
+            .with_env("AUTOMATIC_MODE", "test")
+            .with_env_remove("HOME");
+
+        assert!(Condition::new().holds(&item));
+        assert!(Condition::new()
+            .with_exists("test_script.yaml")
+            .holds(&item));
+        assert!(!Condition::new().with_exists("Cargo.toml").holds(&item));
+        assert!(Condition::new().with_env("AUTOMATIC_MODE").holds(&item));
+        assert!(!Condition::new().with_env("HOME").holds(&item));
+        assert!(Condition::new()
+            .with_env_equals("AUTOMATIC_MODE", "test")
+            .with_os(env::consts::OS)
+            .with_arch(env::consts::ARCH)
+            .holds(&item));
+        assert!(!Condition::new()
+            .with_env_equals("AUTOMATIC_MODE", "other")
+            .holds(&item));
+    }
+
+    #[test]
+    fn serde_condition() {
+        let condition: Condition =
+            serde_yaml::from_str("exists: Cargo.toml\nenv_equals:\n  CI: \"true\"\nos: linux\n")
+                .unwrap();
+
+        assert_eq!(
+            condition,
+            Condition::new()
+                .with_exists("Cargo.toml")
+                .with_env_equals("CI", "true")
+                .with_os("linux")
+        );
+    }
+}
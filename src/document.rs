@@ -0,0 +1,746 @@
+//! Script document as tree of nodes with their positions, check reads the
+//! tree in one pass: every node is read on its own, so error of item is kept
+//! and parse goes on with the next item
+
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use serde::de::{
+    self, DeserializeOwned, DeserializeSeed, Deserializer, EnumAccess, MapAccess, SeqAccess,
+    Unexpected, VariantAccess, Visitor,
+};
+use serde::Deserialize;
+use yaml_rust::parser::{Event, MarkedEventReceiver, Parser};
+use yaml_rust::scanner::{Marker, TScalarStyle, TokenType};
+
+use crate::error::Error as ScriptError;
+use crate::format::Format;
+
+thread_local! {
+    /// Errors of items while document is checked, `None` out of check
+    static ITEM_ERRORS: RefCell<Option<Vec<String>>> = const { RefCell::new(None) };
+}
+
+/// Result of parse of item, while document is checked error of item is kept
+/// and the item is replaced by `placeholder`, so parse goes on
+pub(crate) fn recover<T, E: de::Error, F: FnOnce() -> T>(
+    result: Result<T, E>,
+    placeholder: F,
+) -> Result<T, E> {
+    let error = match result {
+        Ok(value) => return Ok(value),
+        Err(error) => error,
+    };
+    ITEM_ERRORS.with(|errors| match errors.borrow_mut().as_mut() {
+        Some(errors) => {
+            errors.push(error.to_string());
+            Ok(placeholder())
+        }
+        None => Err(error),
+    })
+}
+
+/// Node of document with its `(line, column)` in source
+#[derive(Clone)]
+struct Node {
+    kind: Kind,
+    location: Option<(usize, usize)>,
+}
+
+#[derive(Clone)]
+enum Kind {
+    /// Yaml scalar as document of its own, yaml parser reads it, so plain
+    /// scalars are resolved as in script
+    Yaml(String),
+    /// Json or toml scalar
+    Value(serde_json::Value),
+    Seq(Vec<Node>),
+    Map(Vec<Field>),
+}
+
+/// Field of map node, `id` tells it among fields of document
+#[derive(Clone)]
+struct Field {
+    id: usize,
+    /// Key as it's written in script, `None` if key isn't scalar
+    name: Option<String>,
+    key: Node,
+    value: Node,
+}
+
+/// Script document, fields with errors are left out of its next parse
+pub(crate) struct Document {
+    root: Option<Node>,
+    dropped: RefCell<BTreeSet<usize>>,
+}
+
+impl Document {
+    pub(crate) fn load(format: Format, source: &str) -> Result<Self, ScriptError> {
+        let parse_error = |location, message| ScriptError::Parse {
+            path: None,
+            location,
+            message,
+        };
+        let root = match format {
+            Format::Yaml | Format::Json => {
+                let mut loader = Loader {
+                    format,
+                    stack: vec![],
+                    anchors: BTreeMap::new(),
+                    root: None,
+                    fields: 0,
+                };
+                Parser::new(source.chars())
+                    .load(&mut loader, false)
+                    .map_err(|e| {
+                        let marker = e.marker();
+                        parse_error(Some((marker.line(), marker.col() + 1)), e.to_string())
+                    })?;
+                loader.root
+            }
+            Format::Toml => {
+                let value = toml::from_str::<toml::Value>(source)
+                    .map_err(|e| parse_error(None, e.to_string()))?;
+                let value =
+                    serde_json::to_value(value).map_err(|e| parse_error(None, e.to_string()))?;
+                Some(value_node(value, &mut 0))
+            }
+        };
+        Ok(Self {
+            root,
+            dropped: RefCell::new(BTreeSet::new()),
+        })
+    }
+
+    /// Errors of parse of document as `T`: errors of items are kept and
+    /// parse goes on, field with other error is left out and document is
+    /// parsed again, error which isn't in field stops parse
+    pub(crate) fn errors<T: DeserializeOwned>(&self) -> Vec<String> {
+        let root = match &self.root {
+            Some(root) => root,
+            None => return vec![],
+        };
+        let collected = Collect::start();
+        let mut errors = vec![];
+        loop {
+            let error = match T::deserialize(NodeDeserializer::new(root, ".".to_string(), self)) {
+                Ok(_) => break,
+                Err(error) => error,
+            };
+            // Error out of field after fields are left out is caused by them
+            if errors.is_empty() || error.field.is_some() {
+                errors.push(error.to_string());
+            }
+            match error.field {
+                Some(id) if self.dropped.borrow_mut().insert(id) => (),
+                _ => break,
+            }
+        }
+        collected.finish().into_iter().chain(errors).collect()
+    }
+}
+
+/// Collection of errors of items on current thread
+struct Collect;
+
+impl Collect {
+    fn start() -> Self {
+        ITEM_ERRORS.with(|errors| *errors.borrow_mut() = Some(vec![]));
+        Collect
+    }
+
+    fn finish(self) -> Vec<String> {
+        ITEM_ERRORS.with(|errors| errors.borrow_mut().take().unwrap_or_default())
+    }
+}
+
+impl Drop for Collect {
+    fn drop(&mut self) {
+        ITEM_ERRORS.with(|errors| *errors.borrow_mut() = None);
+    }
+}
+
+/// Node of json value without position
+fn value_node(value: serde_json::Value, fields: &mut usize) -> Node {
+    let kind = match value {
+        serde_json::Value::Array(items) => Kind::Seq(
+            items
+                .into_iter()
+                .map(|item| value_node(item, fields))
+                .collect(),
+        ),
+        serde_json::Value::Object(map) => Kind::Map(
+            map.into_iter()
+                .map(|(name, value)| {
+                    *fields += 1;
+                    Field {
+                        id: *fields,
+                        key: value_node(serde_json::Value::String(name.clone()), fields),
+                        name: Some(name),
+                        value: value_node(value, fields),
+                    }
+                })
+                .collect(),
+        ),
+        value => Kind::Value(value),
+    };
+    Node {
+        kind,
+        location: None,
+    }
+}
+
+/// Key of map with text of its scalar, it waits for its value
+type Key = Option<(Option<String>, Node)>;
+
+/// Builder of tree of yaml or json document from parser events
+struct Loader {
+    format: Format,
+    /// Open nodes with anchors, maps keep their pending key
+    stack: Vec<(Node, Key, usize)>,
+    anchors: BTreeMap<usize, Node>,
+    root: Option<Node>,
+    fields: usize,
+}
+
+impl Loader {
+    fn scalar(&self, value: String, style: TScalarStyle, tag: Option<TokenType>) -> Kind {
+        if self.format == Format::Json {
+            return Kind::Value(match style {
+                TScalarStyle::Plain => {
+                    serde_json::from_str(&value).unwrap_or(serde_json::Value::String(value))
+                }
+                _ => serde_json::Value::String(value),
+            });
+        }
+        // Scalar which can't be read as plain one on its own is a string
+        // anyway, so it's quoted
+        let plain = style == TScalarStyle::Plain
+            && value.starts_with(|c: char| c.is_ascii_alphanumeric() || "+-.~".contains(c))
+            && value
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "+-._~".contains(c))
+            && value != "-"
+            && !value.starts_with("--")
+            && !value.starts_with("..");
+        let scalar = match plain {
+            true => value,
+            false => serde_json::to_string(&value).unwrap_or_default(),
+        };
+        Kind::Yaml(match tag {
+            Some(TokenType::Tag(handle, suffix)) => format!("{}{} {}", handle, suffix, scalar),
+            _ => scalar,
+        })
+    }
+
+    /// Add node to open node, `name` is text of scalar which can be key
+    fn add(&mut self, node: Node, name: Option<String>) {
+        match self.stack.last_mut() {
+            Some((
+                Node {
+                    kind: Kind::Seq(items),
+                    ..
+                },
+                _,
+                _,
+            )) => items.push(node),
+            Some((
+                Node {
+                    kind: Kind::Map(fields),
+                    ..
+                },
+                key,
+                _,
+            )) => match key.take() {
+                Some((name, key)) => {
+                    self.fields += 1;
+                    fields.push(Field {
+                        id: self.fields,
+                        name,
+                        key,
+                        value: node,
+                    });
+                }
+                None => *key = Some((name, node)),
+            },
+            _ => self.root = Some(node),
+        }
+    }
+}
+
+impl MarkedEventReceiver for Loader {
+    fn on_event(&mut self, event: Event, marker: Marker) {
+        let location = Some((marker.line(), marker.col() + 1));
+        let mut name = None;
+        let (node, anchor) = match event {
+            Event::Scalar(value, style, anchor, tag) => {
+                name = Some(value.clone());
+                let kind = self.scalar(value, style, tag);
+                (Node { kind, location }, anchor)
+            }
+            Event::SequenceStart(anchor) => {
+                let node = Node {
+                    kind: Kind::Seq(vec![]),
+                    location,
+                };
+                self.stack.push((node, None, anchor));
+                return;
+            }
+            Event::MappingStart(anchor) => {
+                let node = Node {
+                    kind: Kind::Map(vec![]),
+                    location,
+                };
+                self.stack.push((node, None, anchor));
+                return;
+            }
+            Event::SequenceEnd | Event::MappingEnd => match self.stack.pop() {
+                Some((node, _, anchor)) => (node, anchor),
+                None => return,
+            },
+            Event::Alias(anchor) => match self.anchors.get(&anchor) {
+                Some(node) => (node.clone(), 0),
+                None => return,
+            },
+            _ => return,
+        };
+        if anchor > 0 {
+            self.anchors.insert(anchor, node.clone());
+        }
+        self.add(node, name);
+    }
+}
+
+/// Error of node with its path and position, it's shown like error of yaml
+/// parser
+#[derive(Debug)]
+pub(crate) struct Error {
+    message: String,
+    /// Path and position of node which has the error
+    node: Option<(String, Option<(usize, usize)>)>,
+    /// Field of document which key or value has the error
+    field: Option<usize>,
+}
+
+impl de::Error for Error {
+    fn custom<T: fmt::Display>(message: T) -> Self {
+        Self {
+            message: message.to_string(),
+            node: None,
+            field: None,
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.node {
+            Some((path, _)) if path != "." => write!(f, "{}: {}", path, self.message)?,
+            _ => write!(f, "{}", self.message)?,
+        }
+        match &self.node {
+            Some((_, Some((line, column)))) => write!(f, " at line {} column {}", line, column),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Error of yaml parser of scalar without position in the scalar
+fn yaml_error(e: serde_yaml::Error) -> Error {
+    let message = e.to_string();
+    let message = match e.location() {
+        Some(location) => message
+            .strip_suffix(&format!(
+                " at line {} column {}",
+                location.line(),
+                location.column()
+            ))
+            .unwrap_or(&message)
+            .to_string(),
+        None => message,
+    };
+    de::Error::custom(message)
+}
+
+/// Deserializer of node at `path` of document
+struct NodeDeserializer<'de> {
+    node: &'de Node,
+    path: String,
+    document: &'de Document,
+}
+
+impl<'de> NodeDeserializer<'de> {
+    fn new(node: &'de Node, path: String, document: &'de Document) -> Self {
+        Self {
+            node,
+            path,
+            document,
+        }
+    }
+
+    /// Set path and position of node to error of node
+    fn fix(&self, mut e: Error) -> Error {
+        if e.node.is_none() {
+            e.node = Some((self.path.clone(), self.node.location));
+        }
+        e
+    }
+
+    fn same(&self) -> Self {
+        Self::new(self.node, self.path.clone(), self.document)
+    }
+
+    fn seq<V: Visitor<'de>>(&self, items: &'de [Node], visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_seq(Items {
+            items: items.iter(),
+            index: 0,
+            path: &self.path,
+            document: self.document,
+        })
+    }
+
+    fn map<V: Visitor<'de>>(&self, fields: &'de [Field], visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_map(Fields {
+            fields: fields.iter(),
+            value: None,
+            path: &self.path,
+            document: self.document,
+        })
+    }
+}
+
+/// Read scalar node by parser of its format, other nodes by `$other`
+macro_rules! scalar {
+    ($self:ident, $visitor:ident, $method:ident($($arg:expr),*), $other:expr) => {
+        match &$self.node.kind {
+            Kind::Yaml(scalar) => serde_yaml::Deserializer::from_str(scalar)
+                .$method($($arg,)* $visitor)
+                .map_err(yaml_error),
+            Kind::Value(value) => value
+                .clone()
+                .$method($($arg,)* $visitor)
+                .map_err(de::Error::custom),
+            _ => $other,
+        }
+        .map_err(|e| $self.fix(e))
+    };
+}
+
+macro_rules! scalar_methods {
+    ($($method:ident)*) => {$(
+        fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+            scalar!(self, visitor, $method(), Err(self.unexpected(&visitor)))
+        }
+    )*};
+}
+
+impl<'de> NodeDeserializer<'de> {
+    /// Error of scalar expected in place of seq or map
+    fn unexpected(&self, expected: &dyn de::Expected) -> Error {
+        match &self.node.kind {
+            Kind::Seq(_) => de::Error::invalid_type(Unexpected::Seq, expected),
+            _ => de::Error::invalid_type(Unexpected::Map, expected),
+        }
+    }
+}
+
+impl<'de> Deserializer<'de> for NodeDeserializer<'de> {
+    type Error = Error;
+
+    scalar_methods! {
+        deserialize_bool deserialize_i8 deserialize_i16 deserialize_i32 deserialize_i64
+        deserialize_u8 deserialize_u16 deserialize_u32 deserialize_u64 deserialize_f32
+        deserialize_f64 deserialize_char deserialize_str deserialize_string deserialize_bytes
+        deserialize_byte_buf deserialize_unit deserialize_identifier
+    }
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        scalar!(
+            self,
+            visitor,
+            deserialize_any(),
+            match &self.node.kind {
+                Kind::Seq(items) => self.seq(items, visitor),
+                Kind::Map(fields) => self.map(fields, visitor),
+                _ => unreachable!(),
+            }
+        )
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        scalar!(
+            self,
+            visitor,
+            deserialize_option(),
+            visitor.visit_some(self.same())
+        )
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        scalar!(
+            self,
+            visitor,
+            deserialize_unit_struct(name),
+            Err(self.unexpected(&visitor))
+        )
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        scalar!(
+            self,
+            visitor,
+            deserialize_newtype_struct(name),
+            visitor.visit_newtype_struct(self.same())
+        )
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        scalar!(
+            self,
+            visitor,
+            deserialize_seq(),
+            match &self.node.kind {
+                Kind::Seq(items) => self.seq(items, visitor),
+                _ => Err(self.unexpected(&visitor)),
+            }
+        )
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value, Error> {
+        scalar!(
+            self,
+            visitor,
+            deserialize_tuple(len),
+            match &self.node.kind {
+                Kind::Seq(items) => self.seq(items, visitor),
+                _ => Err(self.unexpected(&visitor)),
+            }
+        )
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        scalar!(
+            self,
+            visitor,
+            deserialize_tuple_struct(name, len),
+            match &self.node.kind {
+                Kind::Seq(items) => self.seq(items, visitor),
+                _ => Err(self.unexpected(&visitor)),
+            }
+        )
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        scalar!(
+            self,
+            visitor,
+            deserialize_map(),
+            match &self.node.kind {
+                Kind::Map(fields) => self.map(fields, visitor),
+                _ => Err(self.unexpected(&visitor)),
+            }
+        )
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        scalar!(
+            self,
+            visitor,
+            deserialize_struct(name, fields),
+            match &self.node.kind {
+                Kind::Map(fields) => self.map(fields, visitor),
+                _ => Err(self.unexpected(&visitor)),
+            }
+        )
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        scalar!(
+            self,
+            visitor,
+            deserialize_enum(name, variants),
+            match &self.node.kind {
+                Kind::Map(fields) if fields.len() == 1 => visitor.visit_enum(Variant {
+                    field: &fields[0],
+                    path: &self.path,
+                    document: self.document,
+                }),
+                Kind::Map(fields) => Err(de::Error::invalid_length(
+                    fields.len(),
+                    &"map containing 1 entry"
+                )),
+                _ => Err(self.unexpected(&visitor)),
+            }
+        )
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_unit()
+    }
+}
+
+/// Items of seq node
+struct Items<'a, 'de> {
+    items: std::slice::Iter<'de, Node>,
+    index: usize,
+    path: &'a str,
+    document: &'de Document,
+}
+
+impl<'a, 'de> SeqAccess<'de> for Items<'a, 'de> {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, Error> {
+        let item = match self.items.next() {
+            Some(item) => item,
+            None => return Ok(None),
+        };
+        let path = format!("{}[{}]", self.path, self.index);
+        self.index += 1;
+        seed.deserialize(NodeDeserializer::new(item, path, self.document))
+            .map(Some)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.items.len())
+    }
+}
+
+/// Fields of map node which aren't left out
+struct Fields<'a, 'de> {
+    fields: std::slice::Iter<'de, Field>,
+    value: Option<&'de Field>,
+    path: &'a str,
+    document: &'de Document,
+}
+
+/// Path of value of field at `path`
+fn field_path(path: &str, field: &Field) -> String {
+    let name = field.name.as_deref().unwrap_or("?");
+    match path {
+        "." => name.to_string(),
+        path => format!("{}.{}", path, name),
+    }
+}
+
+/// Set field to error of its key or value
+fn in_field(mut e: Error, field: &Field) -> Error {
+    e.field = e.field.or(Some(field.id));
+    e
+}
+
+impl<'a, 'de> MapAccess<'de> for Fields<'a, 'de> {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Error> {
+        let dropped = self.document.dropped.borrow();
+        let field = match self.fields.find(|field| !dropped.contains(&field.id)) {
+            Some(field) => field,
+            None => return Ok(None),
+        };
+        drop(dropped);
+        self.value = Some(field);
+        seed.deserialize(NodeDeserializer::new(
+            &field.key,
+            self.path.to_string(),
+            self.document,
+        ))
+        .map(Some)
+        .map_err(|e| in_field(e, field))
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+        let field = match self.value.take() {
+            Some(field) => field,
+            None => return Err(de::Error::custom("value is missing")),
+        };
+        seed.deserialize(NodeDeserializer::new(
+            &field.value,
+            field_path(self.path, field),
+            self.document,
+        ))
+        .map_err(|e| in_field(e, field))
+    }
+}
+
+/// Variant of enum written as map with the only field
+struct Variant<'a, 'de> {
+    field: &'de Field,
+    path: &'a str,
+    document: &'de Document,
+}
+
+impl<'a, 'de> EnumAccess<'de> for Variant<'a, 'de> {
+    type Error = Error;
+    type Variant = NodeDeserializer<'de>;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(
+        self,
+        seed: V,
+    ) -> Result<(V::Value, Self::Variant), Error> {
+        let key = NodeDeserializer::new(&self.field.key, self.path.to_string(), self.document);
+        let variant = seed.deserialize(key)?;
+        let value = NodeDeserializer::new(
+            &self.field.value,
+            field_path(self.path, self.field),
+            self.document,
+        );
+        Ok((variant, value))
+    }
+}
+
+impl<'de> VariantAccess<'de> for NodeDeserializer<'de> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<(), Error> {
+        <()>::deserialize(self)
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, Error> {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_tuple(len, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_struct("", fields, visitor)
+    }
+}
@@ -0,0 +1,482 @@
+//! Errors of script parsing and run of items
+//!
+//! # Example
+//!```
+//!use automatic::runitem::RunItem;
+//!use automatic::silent_list::SilentList;
+//!use automatic::run::Run;
+//!use automatic::Error;
+//!
+//!let items = vec![
+//!    RunItem::new("true".to_string(), vec![]),
+//!    RunItem::new("false".to_string(), vec![]),
+//!];
+//!
+//!let error = SilentList::new(items).run().status().unwrap_err();
+//!
+//!match error {
+//!    Error::Item { index, ref source } => {
+//!        assert_eq!(index, 1);
+//!        assert!(matches!(**source, Error::NonZeroExit { code: 1, .. }));
+//!    }
+//!    _ => panic!("unexpected error: {}", error),
+//!}
+//!```
+
+use std::error;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::run::signal_name;
+
+/// Exit code of process for script which can't be parsed or has invalid
+/// items, see [`Error::exit_code`]
+pub const EXIT_PARSE: i32 = 65;
+/// Exit code of process for script which can't be read
+pub const EXIT_NO_SCRIPT: i32 = 66;
+/// Exit code of process for item which timed out
+pub const EXIT_TIMEOUT: i32 = 124;
+/// Exit code of process for item which can't be started
+pub const EXIT_SPAWN: i32 = 127;
+/// Exit code of process for cancelled run
+pub const EXIT_CANCELLED: i32 = 130;
+
+/// Exit code of process for item which failed with exit `code`, codes which
+/// don't tell failure to parent process (`0`, negative or multiple of `256`)
+/// are replaced by `1`
+pub fn failure_code(code: i32) -> i32 {
+    match code {
+        1..=255 => code,
+        _ => 1,
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    /// Script file doesn't exist
+    ScriptNotFound(PathBuf),
+    /// Script file can't be opened or read
+    ScriptRead { path: PathBuf, source: io::Error },
+    /// Script has wrong format, location is `(line, column)` counting from 1
+    Parse {
+        path: Option<PathBuf>,
+        location: Option<(usize, usize)>,
+        message: String,
+    },
+    /// Included script failed, `chain` is paths of including scripts from
+    /// the top one to the failed one
+    Include {
+        chain: Vec<PathBuf>,
+        source: Box<Error>,
+    },
+    /// Script includes itself through `chain` of included scripts
+    IncludeCycle { chain: Vec<PathBuf> },
+    /// Script has no target with `name` or no default target if `name` is
+    /// `None`, `targets` are names of script targets
+    UnknownTarget {
+        name: Option<String>,
+        targets: Vec<String>,
+    },
+    /// Script refers to variable which isn't defined
+    UndefinedVariable { name: String },
+    /// Several items of graph list have the same id
+    DuplicateId(String),
+    /// Item of graph list needs item with id which doesn't exist
+    UnknownNeed { item: String, need: String },
+    /// Items of graph list depend on each other
+    Cycle { ids: Vec<String> },
+    /// Item can't be started or waited
+    Spawn { item: String, source: io::Error },
+    /// Item exited with non zero code
+    NonZeroExit { item: String, code: i32 },
+    /// Item was terminated by signal
+    Signaled {
+        item: String,
+        signal: i32,
+        core_dumped: bool,
+    },
+    /// Item was terminated because it runs longer than its timeout
+    Timeout { item: String, timeout: Duration },
+    /// Item was terminated because run was cancelled
+    Cancelled { item: String },
+    /// Every attempt of retried item failed, attempts are in run order
+    Retried { attempts: Vec<Error> },
+    /// Item of list with `index` failed
+    Item { index: usize, source: Box<Error> },
+    /// Some items of parallel list failed
+    Parallel { total: usize, failures: Vec<Error> },
+}
+
+impl Error {
+    /// Error of item run which caused this error without list and retry
+    /// wrappers, errors of parallel list aren't unwrapped
+    pub fn root(&self) -> &Error {
+        match self {
+            Error::Item { source, .. } => source.root(),
+            Error::Retried { attempts } => match attempts.last() {
+                Some(last) => last.root(),
+                None => self,
+            },
+            _ => self,
+        }
+    }
+
+    /// Exit code of process which failed with this error: code of failed
+    /// item, `128 + signal` for item terminated by signal or one of `EXIT_*`
+    /// codes, the first failure of parallel list counts, items cancelled
+    /// because of other failure count only if all failures are cancellations
+    pub fn exit_code(&self) -> i32 {
+        match self.root() {
+            Error::NonZeroExit { code, .. } => failure_code(*code),
+            Error::Signaled { signal, .. } => 128 + signal,
+            Error::Timeout { .. } => EXIT_TIMEOUT,
+            Error::Cancelled { .. } => EXIT_CANCELLED,
+            Error::Spawn { .. } => EXIT_SPAWN,
+            Error::Parallel { failures, .. } => failures
+                .iter()
+                .find(|failure| !matches!(failure.root(), Error::Cancelled { .. }))
+                .or_else(|| failures.first())
+                .map_or(1, Error::exit_code),
+            Error::Include { source, .. } => source.exit_code(),
+            Error::ScriptNotFound(_) | Error::ScriptRead { .. } => EXIT_NO_SCRIPT,
+            Error::Parse { .. }
+            | Error::IncludeCycle { .. }
+            | Error::UnknownTarget { .. }
+            | Error::UndefinedVariable { .. }
+            | Error::DuplicateId(_)
+            | Error::UnknownNeed { .. }
+            | Error::Cycle { .. } => EXIT_PARSE,
+            Error::Retried { .. } | Error::Item { .. } => 1,
+        }
+    }
+
+    /// Short description of item run failure
+    fn outcome(&self) -> String {
+        match self.root() {
+            Error::NonZeroExit { code, .. } => format!("code {}", code),
+            Error::Signaled { signal, .. } => signal_name(*signal),
+            Error::Timeout { .. } => "timed out".to_string(),
+            Error::Cancelled { .. } => "cancelled".to_string(),
+            Error::Spawn { source, .. } => source.to_string(),
+            e => e.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::ScriptNotFound(path) => write!(f, "Script: {} doesn't exist", path.display()),
+            Error::ScriptRead { path, source } => write!(
+                f,
+                "fail to read script {} with error: {}",
+                path.display(),
+                source
+            ),
+            Error::Parse {
+                path: Some(path),
+                message,
+                ..
+            } => write!(f, "fail to parse script {}: {}", path.display(), message),
+            Error::Parse { message, .. } => write!(f, "fail to parse script: {}", message),
+            Error::Include { chain, source } => {
+                write!(f, "{} (include chain: {})", source, display_chain(chain))
+            }
+            Error::IncludeCycle { chain } => write!(f, "include cycle: {}", display_chain(chain)),
+            Error::UnknownTarget { name, targets } => {
+                match name {
+                    Some(name) => write!(f, "unknown target `{}`", name)?,
+                    None => write!(f, "no default target")?,
+                }
+                match targets.is_empty() {
+                    true => write!(f, ", script has no targets"),
+                    false => write!(f, ", available targets: {}", targets.join(", ")),
+                }
+            }
+            Error::UndefinedVariable { name } => write!(f, "undefined variable `{}`", name),
+            Error::DuplicateId(id) => write!(f, "duplicate item id `{}`", id),
+            Error::UnknownNeed { item, need } => {
+                write!(f, "`{}` needs unknown item id `{}`", item, need)
+            }
+            Error::Cycle { ids } => write!(f, "dependency cycle between items: {}", ids.join(", ")),
+            Error::Spawn { item, source } => write!(f, "fail to run `{}`: {}", item, source),
+            Error::NonZeroExit { item, code } => write!(
+                f,
+                "`{}` return fail execution state with code: {}",
+                item, code
+            ),
+            Error::Signaled {
+                item,
+                signal,
+                core_dumped,
+            } => {
+                write!(f, "`{}` terminated by {}", item, signal_name(*signal))?;
+                match core_dumped {
+                    true => write!(f, " (core dumped)"),
+                    false => Ok(()),
+                }
+            }
+            Error::Timeout { item, timeout } => {
+                write!(f, "`{}` timed out after {}s", item, timeout.as_secs_f64())
+            }
+            Error::Cancelled { item } => write!(f, "`{}` cancelled", item),
+            Error::Retried { attempts } => {
+                let outcomes: Vec<String> = attempts.iter().map(Error::outcome).collect();
+                match attempts.last() {
+                    Some(last) => write!(
+                        f,
+                        "{} after {} attempts: {}",
+                        last,
+                        attempts.len(),
+                        outcomes.join(", ")
+                    ),
+                    None => write!(f, "no attempts"),
+                }
+            }
+            Error::Item { index, source } => write!(f, "item #{}: {}", index, source),
+            Error::Parallel { total, failures } => {
+                let failures: Vec<String> = failures.iter().map(Error::to_string).collect();
+                write!(
+                    f,
+                    "{} of {} parallel items failed: {}",
+                    failures.len(),
+                    total,
+                    failures.join("; ")
+                )
+            }
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::ScriptRead { source, .. } | Error::Spawn { source, .. } => Some(source),
+            Error::Item { source, .. } | Error::Include { source, .. } => Some(source.as_ref()),
+            Error::Retried { attempts } => attempts.last().map(|e| e as &dyn error::Error),
+            _ => None,
+        }
+    }
+}
+
+fn display_chain(chain: &[PathBuf]) -> String {
+    let chain: Vec<String> = chain
+        .iter()
+        .map(|path| path.display().to_string())
+        .collect();
+    chain.join(" -> ")
+}
+
+fn clone_io(e: &io::Error) -> io::Error {
+    match e.raw_os_error() {
+        Some(code) => io::Error::from_raw_os_error(code),
+        None => io::Error::new(e.kind(), e.to_string()),
+    }
+}
+
+impl Clone for Error {
+    fn clone(&self) -> Self {
+        match self {
+            Error::ScriptNotFound(path) => Error::ScriptNotFound(path.clone()),
+            Error::ScriptRead { path, source } => Error::ScriptRead {
+                path: path.clone(),
+                source: clone_io(source),
+            },
+            Error::Parse {
+                path,
+                location,
+                message,
+            } => Error::Parse {
+                path: path.clone(),
+                location: *location,
+                message: message.clone(),
+            },
+            Error::Include { chain, source } => Error::Include {
+                chain: chain.clone(),
+                source: source.clone(),
+            },
+            Error::IncludeCycle { chain } => Error::IncludeCycle {
+                chain: chain.clone(),
+            },
+            Error::UnknownTarget { name, targets } => Error::UnknownTarget {
+                name: name.clone(),
+                targets: targets.clone(),
+            },
+            Error::UndefinedVariable { name } => Error::UndefinedVariable { name: name.clone() },
+            Error::DuplicateId(id) => Error::DuplicateId(id.clone()),
+            Error::UnknownNeed { item, need } => Error::UnknownNeed {
+                item: item.clone(),
+                need: need.clone(),
+            },
+            Error::Cycle { ids } => Error::Cycle { ids: ids.clone() },
+            Error::Spawn { item, source } => Error::Spawn {
+                item: item.clone(),
+                source: clone_io(source),
+            },
+            Error::NonZeroExit { item, code } => Error::NonZeroExit {
+                item: item.clone(),
+                code: *code,
+            },
+            Error::Signaled {
+                item,
+                signal,
+                core_dumped,
+            } => Error::Signaled {
+                item: item.clone(),
+                signal: *signal,
+                core_dumped: *core_dumped,
+            },
+            Error::Timeout { item, timeout } => Error::Timeout {
+                item: item.clone(),
+                timeout: *timeout,
+            },
+            Error::Cancelled { item } => Error::Cancelled { item: item.clone() },
+            Error::Retried { attempts } => Error::Retried {
+                attempts: attempts.clone(),
+            },
+            Error::Item { index, source } => Error::Item {
+                index: *index,
+                source: source.clone(),
+            },
+            Error::Parallel { total, failures } => Error::Parallel {
+                total: *total,
+                failures: failures.clone(),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display() {
+        let error = Error::Item {
+            index: 2,
+            source: Box::new(Error::Retried {
+                attempts: vec![
+                    Error::NonZeroExit {
+                        item: "curl example.com".to_string(),
+                        code: 6,
+                    },
+                    Error::Timeout {
+                        item: "curl example.com".to_string(),
+                        timeout: Duration::from_millis(1500),
+                    },
+                ],
+            }),
+        };
+
+        assert_eq!(
+            error.to_string(),
+            "item #2: `curl example.com` timed out after 1.5s after 2 attempts: code 6, timed out"
+        );
+        assert!(matches!(error.root(), Error::Timeout { .. }));
+
+        let error = Error::Spawn {
+            item: "bla bla".to_string(),
+            source: io::Error::from_raw_os_error(2),
+        };
+        let cloned = error.clone();
+
+        assert_eq!(cloned.to_string(), error.to_string());
+        assert_eq!(
+            error.to_string(),
+            "fail to run `bla bla`: No such file or directory (os error 2)"
+        );
+    }
+
+    #[test]
+    fn exit_code() {
+        let error = Error::Item {
+            index: 0,
+            source: Box::new(Error::NonZeroExit {
+                item: "false".to_string(),
+                code: 3,
+            }),
+        };
+        assert_eq!(error.exit_code(), 3);
+
+        // Item with success codes fails with code `0`
+        for code in [0, -1, 256] {
+            let error = Error::NonZeroExit {
+                item: "true".to_string(),
+                code,
+            };
+            assert_eq!(error.exit_code(), 1);
+        }
+
+        let error = Error::Parallel {
+            total: 3,
+            failures: vec![
+                Error::Item {
+                    index: 1,
+                    source: Box::new(Error::Signaled {
+                        item: "sleep 10".to_string(),
+                        signal: 9,
+                        core_dumped: false,
+                    }),
+                },
+                Error::Item {
+                    index: 2,
+                    source: Box::new(Error::Timeout {
+                        item: "sleep 10".to_string(),
+                        timeout: Duration::from_secs(1),
+                    }),
+                },
+            ],
+        };
+        assert_eq!(error.exit_code(), 137);
+
+        // Sibling cancelled by fail fast policy doesn't hide the failure
+        let cancelled = Error::Item {
+            index: 0,
+            source: Box::new(Error::Cancelled {
+                item: "sleep 10".to_string(),
+            }),
+        };
+        let error = Error::Parallel {
+            total: 2,
+            failures: vec![
+                cancelled.clone(),
+                Error::Item {
+                    index: 1,
+                    source: Box::new(Error::NonZeroExit {
+                        item: "sh".to_string(),
+                        code: 3,
+                    }),
+                },
+            ],
+        };
+        assert_eq!(error.exit_code(), 3);
+        let error = Error::Parallel {
+            total: 2,
+            failures: vec![cancelled],
+        };
+        assert_eq!(error.exit_code(), EXIT_CANCELLED);
+
+        let error = Error::Include {
+            chain: vec![PathBuf::from("a.yaml"), PathBuf::from("b.yaml")],
+            source: Box::new(Error::ScriptNotFound(PathBuf::from("b.yaml"))),
+        };
+        assert_eq!(error.exit_code(), EXIT_NO_SCRIPT);
+        assert_eq!(
+            Error::UndefinedVariable {
+                name: "A".to_string()
+            }
+            .exit_code(),
+            EXIT_PARSE
+        );
+        assert_eq!(
+            Error::Spawn {
+                item: "bla".to_string(),
+                source: io::Error::from_raw_os_error(2),
+            }
+            .exit_code(),
+            EXIT_SPAWN
+        );
+    }
+}
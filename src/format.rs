@@ -0,0 +1,188 @@
+//! Formats of script files: yaml, json and toml
+//!
+//! # Example
+//!```
+//!use std::path::Path;
+//!
+//!use automatic::format::Format;
+//!use automatic::script::List;
+//!use automatic::silent_list::SilentList;
+//!
+//!let format = Format::from_path(Path::new("script.toml"));
+//!assert_eq!(format, Format::Toml);
+//!
+//!let list = List::Silent(SilentList::new(vec![]));
+//!let encoded = format.to_string(&list).unwrap();
+//!assert_eq!(encoded, "[Silent]\nitems = []\n");
+//!
+//!let decoded: List = format.from_str(&encoded).unwrap();
+//!assert_eq!(decoded, list);
+//!```
+
+use std::fmt;
+use std::marker::PhantomData;
+use std::path::Path;
+use std::str::FromStr;
+
+use serde::de::{DeserializeOwned, DeserializeSeed};
+use serde::Serialize;
+
+use crate::error::Error;
+
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum Format {
+    #[default]
+    Yaml,
+    Json,
+    Toml,
+}
+
+impl Format {
+    /// Format of file by its extension, yaml for unknown extensions
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("json") => Format::Json,
+            Some("toml") => Format::Toml,
+            _ => Format::Yaml,
+        }
+    }
+
+    /// Deserialize value, error has no path of script
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str<T: DeserializeOwned>(&self, s: &str) -> Result<T, Error> {
+        self.decode_seed(s, PhantomData)
+    }
+
+    /// Deserialize value by `seed`, error has no path of script
+    pub(crate) fn decode_seed<'de, S: DeserializeSeed<'de>>(
+        &self,
+        s: &'de str,
+        seed: S,
+    ) -> Result<S::Value, Error> {
+        let parse_error = |location, message| Error::Parse {
+            path: None,
+            location,
+            message,
+        };
+        match self {
+            Format::Yaml => seed
+                .deserialize(serde_yaml::Deserializer::from_str(s))
+                .map_err(|e| {
+                    parse_error(e.location().map(|l| (l.line(), l.column())), e.to_string())
+                }),
+            Format::Json => {
+                let mut deserializer = serde_json::Deserializer::from_str(s);
+                seed.deserialize(&mut deserializer)
+                    .and_then(|value| deserializer.end().map(|_| value))
+                    .map_err(|e| parse_error(Some((e.line(), e.column())), e.to_string()))
+            }
+            // toml deserializer does not support enum variants with nested
+            // tables, json value does
+            Format::Toml => toml::from_str::<toml::Value>(s)
+                .map_err(|e| {
+                    parse_error(
+                        e.line_col().map(|(line, col)| (line + 1, col + 1)),
+                        e.to_string(),
+                    )
+                })
+                .and_then(|value| {
+                    serde_json::to_value(value)
+                        .and_then(|value| seed.deserialize(value))
+                        .map_err(|e| parse_error(None, e.to_string()))
+                }),
+        }
+    }
+
+    pub fn to_string<T: Serialize>(&self, value: &T) -> Result<String, Error> {
+        let serialize_error = |message: String| Error::Parse {
+            path: None,
+            location: None,
+            message,
+        };
+        match self {
+            Format::Yaml => {
+                serde_yaml::to_string(value).map_err(|e| serialize_error(e.to_string()))
+            }
+            Format::Json => serde_json::to_string_pretty(value)
+                .map(|s| s + "\n")
+                .map_err(|e| serialize_error(e.to_string())),
+            // toml serializer does not support enum variants, json value
+            // represents them as tables
+            Format::Toml => serde_json::to_value(value)
+                .map_err(|e| serialize_error(e.to_string()))
+                .and_then(|value| {
+                    toml::Value::try_from(value).map_err(|e| serialize_error(e.to_string()))
+                })
+                .map(|value| value.to_string()),
+        }
+    }
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "yaml" | "yml" => Ok(Format::Yaml),
+            "json" => Ok(Format::Json),
+            "toml" => Ok(Format::Toml),
+            _ => Err(format!(
+                "unknown format `{}`, expected yaml, json or toml",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Format::Yaml => write!(f, "yaml"),
+            Format::Json => write!(f, "json"),
+            Format::Toml => write!(f, "toml"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::runitem::{Defaults, RunItem};
+    use crate::script::List;
+    use crate::silent_list::SilentList;
+
+    use super::*;
+
+    #[test]
+    fn convert() {
+        let list = List::Silent(
+            SilentList::new(vec![
+                RunItem::new("echo".to_string(), vec!["1".to_string()])
+                    .with_env("A", "1")
+                    .with_cwd("src"),
+                RunItem::shell("ls | wc -l".to_string()),
+            ])
+            .with_defaults(Defaults::new().with_env("B", "2")),
+        );
+
+        for format in [Format::Yaml, Format::Json, Format::Toml].iter() {
+            let encoded = format.to_string(&list).unwrap();
+            let decoded: List = format.from_str(&encoded).unwrap();
+            assert_eq!(decoded, list, "{}", format);
+        }
+
+        match Format::Json
+            .from_str::<List>("{\n\"Silent\": 1}")
+            .unwrap_err()
+        {
+            Error::Parse { location, .. } => assert_eq!(location, Some((2, 11))),
+            e => panic!("unexpected error: {}", e),
+        }
+        match Format::Toml
+            .from_str::<List>("[Silent]\nitems = [\n")
+            .unwrap_err()
+        {
+            Error::Parse { location, .. } => assert_eq!(location.map(|(line, _)| line), Some(3)),
+            e => panic!("unexpected error: {}", e),
+        }
+    }
+}
@@ -0,0 +1,391 @@
+//! Graph of run items, no output, item runs after all items from its `needs`
+//! succeed, independent items run simultaneously (up to `max_jobs` at once),
+//! dependents of failed items are skipped
+//!
+//! # Example
+//!```
+//!use automatic::graph_list::GraphList;
+//!use automatic::runitem::RunItem;
+//!use automatic::run::Run;
+//!let items = vec![
+//!    RunItem::new("true".to_string(), vec![]).with_id("test").with_needs(vec!["build"]),
+//!    RunItem::new("true".to_string(), vec![]).with_id("build"),
+//!    RunItem::new("true".to_string(), vec![]).with_id("docs"),
+//!];
+//!
+//!let list = GraphList::new(items);
+//!assert_eq!(list.order().unwrap(), vec![1, 2, 0]);
+//!
+//!let result = list.run().status().expect("failed to execute process");
+//!assert!(result.success());
+//!```
+
+use std::collections::BTreeMap;
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::error::Error;
+use crate::item::{Context, RunIn};
+use crate::report::Report;
+use crate::run::{check_exit, ExitStatus, Run, RunMap, RunStatus};
+use crate::runitem::{Defaults, RunItem};
+use crate::vars::Vars;
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, JsonSchema, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct GraphList {
+    items: Vec<RunItem>,
+    #[serde(default, skip_serializing_if = "Defaults::is_empty")]
+    defaults: Defaults,
+    /// Maximum count of simultaneously running items, count of CPUs if unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    max_jobs: Option<usize>,
+}
+
+/// Run state of graph node
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum State {
+    Pending,
+    Running,
+    Succeeded,
+    Failed,
+    Skipped,
+}
+
+impl GraphList {
+    pub fn new(items: Vec<RunItem>) -> Self {
+        Self {
+            items,
+            defaults: Defaults::new(),
+            max_jobs: None,
+        }
+    }
+
+    pub fn with_defaults(mut self, defaults: Defaults) -> Self {
+        self.defaults = defaults;
+        self
+    }
+
+    pub fn with_max_jobs(mut self, max_jobs: usize) -> Self {
+        self.max_jobs = Some(max_jobs);
+        self
+    }
+
+    pub fn items(&self) -> &[RunItem] {
+        &self.items
+    }
+
+    pub fn defaults(&self) -> &Defaults {
+        &self.defaults
+    }
+
+    pub(crate) fn interpolate(&mut self, vars: &Vars) -> Result<(), Error> {
+        self.defaults.interpolate(vars)?;
+        for item in self.items.iter_mut() {
+            item.interpolate(vars)?;
+        }
+        Ok(())
+    }
+
+    /// Indexes of items in topological order, items without dependencies
+    /// between them keep their order in list
+    pub fn order(&self) -> Result<Vec<usize>, Error> {
+        let mut ids = BTreeMap::new();
+        for (index, item) in self.items.iter().enumerate() {
+            if let Some(id) = item.id() {
+                if ids.insert(id, index).is_some() {
+                    return Err(Error::DuplicateId(id.to_string()));
+                }
+            }
+        }
+
+        let mut needs = vec![vec![]; self.items.len()];
+        for (index, item) in self.items.iter().enumerate() {
+            for need in item.needs() {
+                match ids.get(need.as_str()) {
+                    Some(&need) => needs[index].push(need),
+                    None => {
+                        return Err(Error::UnknownNeed {
+                            item: item.to_string(),
+                            need: need.clone(),
+                        })
+                    }
+                }
+            }
+        }
+
+        let mut order = Vec::with_capacity(self.items.len());
+        let mut ordered = vec![false; self.items.len()];
+        while order.len() < self.items.len() {
+            let ready: Vec<usize> = (0..self.items.len())
+                .filter(|&index| !ordered[index] && needs[index].iter().all(|&need| ordered[need]))
+                .collect();
+            if ready.is_empty() {
+                return Err(Error::Cycle {
+                    ids: (0..self.items.len())
+                        .filter(|&index| !ordered[index])
+                        .map(|index| match self.items[index].id() {
+                            Some(id) => id.to_string(),
+                            None => self.items[index].to_string(),
+                        })
+                        .collect(),
+                });
+            }
+            for index in ready {
+                ordered[index] = true;
+                order.push(index);
+            }
+        }
+        Ok(order)
+    }
+
+    fn jobs(&self) -> usize {
+        let jobs = match self.max_jobs {
+            Some(jobs) => jobs,
+            None => thread::available_parallelism().map_or(1, |jobs| jobs.get()),
+        };
+        jobs.clamp(1, self.items.len().max(1))
+    }
+
+    fn run_internal(&self, ctx: &Context, report: &mut Report) -> Result<(), Error> {
+        let order = self.order()?;
+        let ids: BTreeMap<&str, usize> = self
+            .items
+            .iter()
+            .enumerate()
+            .filter_map(|(index, item)| item.id().map(|id| (id, index)))
+            .collect();
+
+        let mut states = vec![State::Pending; self.items.len()];
+        let mut reports: Vec<Report> = self.items.iter().map(|_| Report::new()).collect();
+        let mut failures = vec![];
+        let (sender, receiver) = mpsc::channel();
+
+        thread::scope(|scope| {
+            let mut running = 0;
+            loop {
+                for &index in order.iter() {
+                    if states[index] != State::Pending {
+                        continue;
+                    }
+                    let item = &self.items[index];
+                    let needs: Vec<State> = item
+                        .needs()
+                        .iter()
+                        .map(|need| states[ids[need.as_str()]])
+                        .collect();
+                    if needs
+                        .iter()
+                        .any(|state| matches!(state, State::Failed | State::Skipped))
+                    {
+                        states[index] = State::Skipped;
+                        reports[index] = item.skipped().report();
+                    } else if running < self.jobs()
+                        && needs.iter().all(|state| *state == State::Succeeded)
+                    {
+                        states[index] = State::Running;
+                        running += 1;
+                        let sender = sender.clone();
+                        scope.spawn(move || {
+                            let (result, item_report) = run_item(item, ctx);
+                            sender.send((index, result, item_report)).unwrap_or(());
+                        });
+                    }
+                }
+
+                if running == 0 {
+                    break;
+                }
+                let (index, result, item_report) = match receiver.recv() {
+                    Ok(message) => message,
+                    Err(e) => {
+                        failures.push(Error::Spawn {
+                            item: "graph list".to_string(),
+                            source: io::Error::other(e),
+                        });
+                        break;
+                    }
+                };
+                running -= 1;
+                reports[index] = item_report;
+                states[index] = match result {
+                    Ok(()) => State::Succeeded,
+                    Err(e) => {
+                        failures.push(Error::Item {
+                            index,
+                            source: Box::new(e),
+                        });
+                        State::Failed
+                    }
+                };
+            }
+        });
+
+        for item_report in reports {
+            report.extend(item_report);
+        }
+
+        if failures.is_empty() {
+            return Ok(());
+        }
+
+        failures.sort_by_key(|e| match e {
+            Error::Item { index, .. } => *index,
+            _ => 0,
+        });
+        Err(Error::Parallel {
+            total: self.items.len(),
+            failures,
+        })
+    }
+}
+
+/// Run item of graph in worker thread, panicked run is failure of item so
+/// main loop still gets its result
+fn run_item(item: &RunItem, ctx: &Context) -> (Result<(), Error>, Report) {
+    panic::catch_unwind(AssertUnwindSafe(|| {
+        let status = item.inherit(&ctx.defaults).run_map(|r| ctx.configure(r));
+        (check_exit(item, &*status), status.report())
+    }))
+    .unwrap_or_else(|_| {
+        let status = item.status(
+            vec![Error::Spawn {
+                item: item.to_string(),
+                source: io::Error::other("run of item panicked"),
+            }
+            .into()],
+            Duration::default(),
+        );
+        (check_exit(item, &*status), status.report())
+    })
+}
+
+struct StatusHelper {}
+
+impl ExitStatus for StatusHelper {}
+
+struct RunItemStatus {
+    status: Result<(), Error>,
+    report: Report,
+}
+
+impl RunStatus for RunItemStatus {
+    fn status(&self) -> Result<Box<dyn ExitStatus>, Error> {
+        match &self.status {
+            Ok(_) => Ok(Box::new(StatusHelper {})),
+            Err(e) => Err(e.clone()),
+        }
+    }
+
+    fn report(&self) -> Report {
+        self.report.clone()
+    }
+}
+
+impl RunIn for GraphList {
+    fn run_in(&self, ctx: &Context) -> Box<dyn RunStatus> {
+        let mut report = Report::new();
+        let status = self.run_internal(&ctx.nested(&self.defaults), &mut report);
+        Box::new(RunItemStatus { status, report })
+    }
+}
+
+impl Run for GraphList {
+    fn run(&self) -> Box<dyn RunStatus> {
+        self.run_in(&Context::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::*;
+
+    fn sh(id: &str, script: &str) -> RunItem {
+        RunItem::new("sh".to_string(), vec!["-c".to_string(), script.to_string()]).with_id(id)
+    }
+
+    #[test]
+    fn order() {
+        let list = GraphList::new(vec![
+            sh("c", "true").with_needs(vec!["a", "b"]),
+            sh("b", "true").with_needs(vec!["a"]),
+            sh("a", "true"),
+        ]);
+        assert_eq!(list.order().unwrap(), vec![2, 1, 0]);
+
+        let list = GraphList::new(vec![
+            sh("a", "true").with_needs(vec!["b"]),
+            sh("b", "true").with_needs(vec!["a"]),
+            sh("c", "true"),
+        ]);
+        assert_eq!(
+            list.order().unwrap_err().to_string(),
+            "dependency cycle between items: a, b"
+        );
+
+        let list = GraphList::new(vec![sh("a", "true"), sh("a", "false")]);
+        assert!(matches!(list.order().unwrap_err(), Error::DuplicateId(id) if id == "a"));
+
+        let list = GraphList::new(vec![sh("a", "true").with_needs(vec!["b"])]);
+        assert_eq!(
+            list.order().unwrap_err().to_string(),
+            "`sh -c true` needs unknown item id `b`"
+        );
+    }
+
+    #[test]
+    fn concurrent_run() {
+        let list = GraphList::new(vec![
+            sh("a", "sleep 0.3"),
+            sh("b", "sleep 0.3"),
+            sh("c", "true").with_needs(vec!["a", "b"]),
+        ])
+        .with_max_jobs(2);
+
+        let start = Instant::now();
+        let result = list.run().status().expect("failed to execute process");
+        assert!(result.success());
+        assert!(start.elapsed() < Duration::from_millis(550));
+
+        let list = GraphList::new(vec![
+            sh("a", "sleep 0.2; echo a > graph_a"),
+            sh("b", "test -f graph_a && rm graph_a").with_needs(vec!["a"]),
+        ])
+        .with_defaults(Defaults::new().with_cwd(std::env::temp_dir()));
+        let result = list.run().status().expect("failed to execute process");
+        assert!(result.success());
+    }
+
+    #[test]
+    fn skip_dependents() {
+        let list = GraphList::new(vec![
+            sh("build", "exit 2"),
+            sh("test", "true").with_needs(vec!["build"]),
+            sh("deploy", "true").with_needs(vec!["test"]),
+            sh("docs", "true"),
+        ]);
+
+        let status = list.run();
+        match status.status().unwrap_err() {
+            Error::Parallel { total, failures } => {
+                assert_eq!(total, 4);
+                assert_eq!(failures.len(), 1);
+                assert!(matches!(failures[0], Error::Item { index: 0, .. }));
+            }
+            e => panic!("unexpected error: {}", e),
+        }
+
+        let report = status.report();
+        let skipped: Vec<bool> = report.items.iter().map(|item| item.skipped).collect();
+        assert_eq!(skipped, vec![false, true, true, false]);
+        assert!(report.items[3].success());
+    }
+}
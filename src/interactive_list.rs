@@ -20,46 +20,76 @@
 
 use std::process::Stdio;
 
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use crate::run::{ExitStatus, Run, RunMap, RunStatus};
-use crate::runitem::RunItem;
+use crate::error::Error;
+use crate::item::{Context, Item, RunIn};
+use crate::report::Report;
+use crate::run::{check_exit, ExitStatus, Run, RunStatus};
+use crate::runitem::{Defaults, RunItem};
+use crate::vars::Vars;
 
-#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+#[derive(Debug, PartialEq, Serialize, Deserialize, JsonSchema, Clone)]
+#[serde(deny_unknown_fields)]
 pub struct InteractiveList {
-    items: Vec<RunItem>,
+    items: Vec<Item>,
+    #[serde(default, skip_serializing_if = "Defaults::is_empty")]
+    defaults: Defaults,
     // TODO: adding removable process pipes storage
 }
 
 impl InteractiveList {
     pub fn new(items: Vec<RunItem>) -> Self {
+        Self::from_items(items.into_iter().map(Item::from).collect())
+    }
+
+    pub fn from_items(items: Vec<Item>) -> Self {
         // TODO: adding setup of removable process pipes
-        Self { items }
+        Self {
+            items,
+            defaults: Defaults::new(),
+        }
+    }
+
+    pub fn with_defaults(mut self, defaults: Defaults) -> Self {
+        self.defaults = defaults;
+        self
+    }
+
+    pub fn items(&self) -> &[Item] {
+        &self.items
+    }
+
+    pub fn defaults(&self) -> &Defaults {
+        &self.defaults
+    }
+
+    pub(crate) fn items_mut(&mut self) -> &mut Vec<Item> {
+        &mut self.items
+    }
+
+    pub(crate) fn interpolate(&mut self, vars: &Vars) -> Result<(), Error> {
+        self.defaults.interpolate(vars)?;
+        for item in self.items.iter_mut() {
+            item.interpolate(vars)?;
+        }
+        Ok(())
     }
 
-    fn run_internal(&self) -> Result<(), String> {
-        for item in self.items.iter() {
-            let result = match item
-                // TODO: rewrite to provide removable process pipes
-                .run_map(|r| {
-                    r.set_stdin(Stdio::inherit())
-                        .set_stdout(Stdio::inherit())
-                        .set_stderr(Stdio::inherit())
-                })
-                .status()
-            {
-                Err(e) => {
-                    return Err(e.to_string());
-                }
-                Ok(result) => result,
-            };
-
-            if !result.success() {
-                return Err(format!(
-                    "runned item return fail execution state with code: {}",
-                    result.code().unwrap()
-                ));
-            }
+    fn run_internal(&self, ctx: &Context, report: &mut Report) -> Result<(), Error> {
+        for (index, item) in self.items.iter().enumerate() {
+            // TODO: rewrite to provide removable process pipes
+            let status = item.run_in(ctx, |r| {
+                r.set_stdin(Stdio::inherit())
+                    .set_stdout(Stdio::inherit())
+                    .set_stderr(Stdio::inherit())
+            });
+            report.extend(status.report());
+            check_exit(item, &*status).map_err(|e| Error::Item {
+                index,
+                source: Box::new(e),
+            })?;
         }
         Ok(())
     }
@@ -70,23 +100,34 @@ struct StatusHelper {}
 impl ExitStatus for StatusHelper {}
 
 struct RunItemStatus {
-    status: Result<(), String>,
+    status: Result<(), Error>,
+    report: Report,
 }
 
 impl RunStatus for RunItemStatus {
-    fn status(&self) -> Result<Box<dyn ExitStatus>, String> {
+    fn status(&self) -> Result<Box<dyn ExitStatus>, Error> {
         match &self.status {
             Ok(_) => Ok(Box::new(StatusHelper {})),
-            Err(e) => Err(e.to_string()),
+            Err(e) => Err(e.clone()),
         }
     }
+
+    fn report(&self) -> Report {
+        self.report.clone()
+    }
+}
+
+impl RunIn for InteractiveList {
+    fn run_in(&self, ctx: &Context) -> Box<dyn RunStatus> {
+        let mut report = Report::new();
+        let status = self.run_internal(&ctx.nested(&self.defaults), &mut report);
+        Box::new(RunItemStatus { status, report })
+    }
 }
 
 impl Run for InteractiveList {
     fn run(&self) -> Box<dyn RunStatus> {
-        Box::new(RunItemStatus {
-            status: self.run_internal(),
-        })
+        self.run_in(&Context::default())
     }
 }
 
@@ -120,9 +161,11 @@ mod tests {
         ];
 
         let runner = InteractiveList::new(items);
+        let error = runner.run().status().unwrap_err();
+        assert!(matches!(error.root(), Error::Spawn { item, .. } if item == "bla bla"));
         assert_eq!(
-            runner.run().status(),
-            Err("No such file or directory (os error 2)".to_string())
+            error.to_string(),
+            "item #1: fail to run `bla bla`: No such file or directory (os error 2)"
         );
 
         let items = vec![
@@ -132,9 +175,11 @@ mod tests {
         ];
 
         let runner = InteractiveList::new(items);
+        let error = runner.run().status().unwrap_err();
+        assert!(matches!(error.root(), Error::NonZeroExit { code: 1, .. }));
         assert_eq!(
-            runner.run().status(),
-            Err("runned item return fail execution state with code: 1".to_string())
+            error.to_string(),
+            "item #1: `false` return fail execution state with code: 1"
         );
     }
 }
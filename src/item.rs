@@ -0,0 +1,481 @@
+//! Item of run list: run item or nested list with its own run mode
+//!
+//! # Example
+//!```
+//!use automatic::interactive_list::InteractiveList;
+//!use automatic::item::Item;
+//!use automatic::runitem::RunItem;
+//!use automatic::run::Run;
+//!use automatic::script::List;
+//!use automatic::silent_list::SilentList;
+//!
+//!let silent = SilentList::new(vec![
+//!    RunItem::new("true".to_string(), vec![]),
+//!    RunItem::new("true".to_string(), vec![]),
+//!]);
+//!
+//!let items: Vec<Item> = vec![
+//!    List::Silent(silent).into(),
+//!    RunItem::new("true".to_string(), vec![]).into(),
+//!];
+//!
+//!let result = InteractiveList::from_items(items)
+//!    .run()
+//!    .status()
+//!    .expect("failed to execute process");
+//!assert!(result.success());
+//!```
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use schemars::JsonSchema;
+use serde::de::value::MapAccessDeserializer;
+use serde::de::{self, MapAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::condition::Condition;
+use crate::document;
+use crate::error::Error;
+use crate::graph_list::GraphList;
+use crate::interactive_list::InteractiveList;
+use crate::parallel_list::ParallelList;
+use crate::pipeline_list::PipelineList;
+use crate::promiscuous_list::PromiscuousList;
+use crate::retry::Retry;
+use crate::run::{ExitStatus, Run, RunMap, RunStatus};
+use crate::runitem::{deserialize_seconds, Defaults, RunItem};
+use crate::runner::{Cancel, Runner};
+use crate::script::{List, Script};
+use crate::silent_list::SilentList;
+use crate::vars::Vars;
+
+#[derive(Debug, PartialEq, Serialize, JsonSchema, Clone)]
+#[serde(untagged)]
+pub enum Item {
+    Run(RunItem),
+    List(List),
+    Include(Include),
+}
+
+impl<'de> Deserialize<'de> for Item {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        document::recover(
+            deserializer.deserialize_map(FieldsVisitor(PhantomData)),
+            || RunItem::shell(String::new()).into(),
+        )
+    }
+}
+
+impl FromFields for Item {
+    fn from_fields<E: de::Error>(mut fields: ItemFields) -> Result<Self, E> {
+        let mut lists = fields.take_lists();
+        if let Some(list) = lists.pop() {
+            return match lists.pop() {
+                Some(other) => Err(E::custom(format!(
+                    "item has more than one list: {} and {}",
+                    other, list
+                ))),
+                None if fields != ItemFields::default() => Err(E::custom(format!(
+                    "item has {} together with other fields",
+                    list
+                ))),
+                None => Ok(Item::List(list)),
+            };
+        }
+        let target = fields.target.take();
+        match fields.include.take() {
+            Some(_) if fields != ItemFields::default() => Err(E::custom(
+                "item has `include` together with fields of run item",
+            )),
+            Some(include) => Ok(Item::Include(Include { include, target })),
+            None if target.is_some() => Err(E::custom("item has `target` without `include`")),
+            None => RunItem::from_fields(fields).map(Item::Run),
+        }
+    }
+}
+
+/// Value which is made of fields of item after all of them are read
+pub(crate) trait FromFields: Sized {
+    fn from_fields<E: de::Error>(fields: ItemFields) -> Result<Self, E>;
+}
+
+/// Visitor of item map, errors of [`FromFields`] are raised while the map is
+/// read, so they keep position of item
+pub(crate) struct FieldsVisitor<T>(pub(crate) PhantomData<T>);
+
+impl<'de, T: FromFields> Visitor<'de> for FieldsVisitor<T> {
+    type Value = T;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "run item, list or include")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<T, A::Error> {
+        T::from_fields(ItemFields::deserialize(MapAccessDeserializer::new(map))?)
+    }
+}
+
+/// Fields of run item, include and lists as they are written in script,
+/// kind of item is told after all fields are read, so errors of fields keep
+/// their position and fields can go in any order
+#[derive(Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct ItemFields {
+    #[serde(default)]
+    pub(crate) id: Option<String>,
+    #[serde(default)]
+    pub(crate) needs: Vec<String>,
+    pub(crate) name: Option<String>,
+    pub(crate) args: Option<Vec<String>>,
+    pub(crate) shell: Option<String>,
+    #[serde(default)]
+    pub(crate) env: BTreeMap<String, String>,
+    pub(crate) env_clear: Option<bool>,
+    #[serde(default)]
+    pub(crate) env_remove: Vec<String>,
+    pub(crate) cwd: Option<PathBuf>,
+    #[serde(default, deserialize_with = "deserialize_seconds")]
+    pub(crate) timeout: Option<f64>,
+    pub(crate) process_group: Option<bool>,
+    pub(crate) retry: Option<Retry>,
+    #[serde(default)]
+    pub(crate) success_codes: Vec<i32>,
+    pub(crate) ignore_failure: Option<bool>,
+    pub(crate) when: Option<Box<Condition>>,
+    pub(crate) unless: Option<Box<Condition>>,
+    pub(crate) when_cmd: Option<String>,
+    pub(crate) include: Option<PathBuf>,
+    pub(crate) target: Option<String>,
+    #[serde(rename = "Interactive")]
+    interactive: Option<InteractiveList>,
+    #[serde(rename = "Promiscuous")]
+    promiscuous: Option<PromiscuousList>,
+    #[serde(rename = "Silent")]
+    silent: Option<SilentList>,
+    #[serde(rename = "Parallel")]
+    parallel: Option<ParallelList>,
+    #[serde(rename = "Pipeline")]
+    pipeline: Option<PipelineList>,
+    #[serde(rename = "Graph")]
+    graph: Option<GraphList>,
+}
+
+impl ItemFields {
+    /// Take out lists of item in order of [`List::KINDS`]
+    pub(crate) fn take_lists(&mut self) -> Vec<List> {
+        let lists = vec![
+            self.interactive.take().map(List::Interactive),
+            self.promiscuous.take().map(List::Promiscuous),
+            self.silent.take().map(List::Silent),
+            self.parallel.take().map(List::Parallel),
+            self.pipeline.take().map(List::Pipeline),
+            self.graph.take().map(List::Graph),
+        ];
+        lists.into_iter().flatten().collect()
+    }
+}
+
+/// List of other script file, `Script::parse` replaces it with the list,
+/// relative path is resolved against directory of including script
+#[derive(Debug, PartialEq, Serialize, Deserialize, JsonSchema, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Include {
+    include: PathBuf,
+    /// Target of included script, its default target if unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    target: Option<String>,
+}
+
+impl Include {
+    pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+        Self {
+            include: path.into(),
+            target: None,
+        }
+    }
+
+    pub fn with_target<S: Into<String>>(mut self, target: S) -> Self {
+        self.target = Some(target.into());
+        self
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.include
+    }
+
+    pub fn target(&self) -> Option<&str> {
+        self.target.as_deref()
+    }
+
+    /// Script of include, relative path is resolved against `dir`
+    pub(crate) fn script(&self, dir: &Path, vars: &Vars) -> Script {
+        let script = Script::new(dir.join(&self.include)).with_vars(vars.clone());
+        match &self.target {
+            Some(target) => script.with_target(target.clone()),
+            None => script,
+        }
+    }
+}
+
+impl From<RunItem> for Item {
+    fn from(item: RunItem) -> Self {
+        Item::Run(item)
+    }
+}
+
+impl From<List> for Item {
+    fn from(list: List) -> Self {
+        Item::List(list)
+    }
+}
+
+impl From<Include> for Item {
+    fn from(include: Include) -> Self {
+        Item::Include(include)
+    }
+}
+
+impl fmt::Display for Item {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Item::Run(item) => item.fmt(f),
+            Item::List(list) => list.fmt(f),
+            Item::Include(include) => write!(f, "include {}", include.include.display()),
+        }
+    }
+}
+
+/// Parameters passed from list to its items
+#[derive(Clone, Default)]
+pub(crate) struct Context {
+    pub(crate) defaults: Defaults,
+    pub(crate) cancel: Vec<Cancel>,
+    /// Time between termination request and kill of cancelled process
+    pub(crate) grace: Option<Duration>,
+}
+
+impl Context {
+    /// Context for items of nested list with its own defaults
+    pub(crate) fn nested(&self, defaults: &Defaults) -> Self {
+        Self {
+            defaults: defaults.inherit(&self.defaults),
+            cancel: self.cancel.clone(),
+            grace: self.grace,
+        }
+    }
+
+    pub(crate) fn with_cancel(&self, cancel: Cancel) -> Self {
+        let mut ctx = self.clone();
+        ctx.cancel.push(cancel);
+        ctx
+    }
+
+    /// Apply cancel flags and grace period of context to runner
+    pub(crate) fn configure<'a>(&self, runner: &'a mut Runner) -> &'a mut Runner {
+        for cancel in self.cancel.iter() {
+            runner.add_cancel(cancel.clone());
+        }
+        if let Some(grace) = self.grace {
+            runner.set_grace(grace);
+        }
+        runner
+    }
+}
+
+/// Run item of tree in context of its parent list
+pub(crate) trait RunIn {
+    fn run_in(&self, ctx: &Context) -> Box<dyn RunStatus>;
+}
+
+impl Item {
+    pub(crate) fn interpolate(&mut self, vars: &Vars) -> Result<(), Error> {
+        match self {
+            Item::Run(item) => item.interpolate(vars),
+            Item::List(list) => list.interpolate(vars),
+            Item::Include(include) => vars.interpolate_path(&mut include.include),
+        }
+    }
+
+    /// Run item in context of parent list, `op` maps runner of run item and
+    /// it's ignored by nested list which uses its own run mode
+    pub(crate) fn run_in<F: FnOnce(&mut Runner) -> &mut Runner>(
+        &self,
+        ctx: &Context,
+        op: F,
+    ) -> Box<dyn RunStatus> {
+        match self {
+            Item::Run(item) => item
+                .inherit(&ctx.defaults)
+                .run_map(|r| op(ctx.configure(r))),
+            Item::List(list) => list.run_in(ctx),
+            Item::Include(include) => match include.script(Path::new(""), &Vars::new()).parse() {
+                Ok(list) => list.run_in(ctx),
+                Err(error) => Box::new(IncludeStatus { error }),
+            },
+        }
+    }
+}
+
+/// Status of include which script can't be parsed
+struct IncludeStatus {
+    error: Error,
+}
+
+impl RunStatus for IncludeStatus {
+    fn status(&self) -> Result<Box<dyn ExitStatus>, Error> {
+        Err(self.error.clone())
+    }
+}
+
+impl Run for Item {
+    fn run(&self) -> Box<dyn RunStatus> {
+        self.run_in(&Context::default(), |r| r)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::promiscuous_list::PromiscuousList;
+    use crate::silent_list::SilentList;
+
+    use super::*;
+
+    #[test]
+    fn serde_item() {
+        let items: Vec<Item> = vec![
+            RunItem::new("true".to_string(), vec![]).into(),
+            List::Silent(SilentList::new(vec![RunItem::new(
+                "true".to_string(),
+                vec![],
+            )]))
+            .into(),
+        ];
+
+        let encoded = serde_yaml::to_string(&items).unwrap();
+
+        assert_eq!(
+            encoded,
+            "---\n- name: \"true\"\
+             \n  args: []\
+             \n- Silent:\
+             \n    items:\
+             \n      - name: \"true\"\
+             \n        args: []\n"
+        );
+
+        let decoded: Vec<Item> = serde_yaml::from_str(&encoded).unwrap();
+
+        assert_eq!(decoded, items);
+    }
+
+    #[test]
+    fn deserialize_item() {
+        let items: Vec<Item> = serde_yaml::from_str(
+            "---\n- target: test\n  include: tests/test_script.yaml\n- id: a\n  shell: \"true\"\n",
+        )
+        .unwrap();
+        assert_eq!(
+            items,
+            vec![
+                Include::new("tests/test_script.yaml")
+                    .with_target("test")
+                    .into(),
+                RunItem::shell("true".to_string()).with_id("a").into(),
+            ]
+        );
+
+        let error = |s: &str| {
+            serde_yaml::from_str::<Vec<Item>>(s)
+                .unwrap_err()
+                .to_string()
+        };
+
+        assert_eq!(
+            error("---\n- shell: \"true\"\n- Silent:\n    items:\n      - name: ls\n        args: ls\n"),
+            ".[1].Silent.items[0].args: invalid type: string \"ls\", expected a sequence \
+             at line 6 column 15"
+        );
+        assert_eq!(
+            error("---\n- include: a.yaml\n  target: [a]\n"),
+            ".[0].target: invalid type: sequence, expected a string at line 3 column 11"
+        );
+        assert_eq!(
+            error("---\n- Silent:\n    items: []\n  cwd: src\n"),
+            ".[0]: item has Silent list together with other fields at line 2 column 9"
+        );
+        assert_eq!(
+            error("---\n- Silent:\n    items: []\n  Graph:\n    items: []\n"),
+            ".[0]: item has more than one list: Silent list and Graph list at line 2 column 9"
+        );
+        assert_eq!(
+            error("---\n- include: a.yaml\n  cwd: src\n"),
+            ".[0]: item has `include` together with fields of run item at line 2 column 10"
+        );
+        assert_eq!(
+            error("---\n- target: a\n  shell: \"true\"\n"),
+            ".[0]: item has `target` without `include` at line 2 column 9"
+        );
+        assert!(error("---\n- Silnt:\n    items: []\n")
+            .starts_with(".[0]: unknown field `Silnt`, expected one of `id`"));
+    }
+
+    #[test]
+    fn deserialize_fields_in_any_order() {
+        let items: Vec<Item> = serde_yaml::from_str(
+            "---\n- id: 5\n  env: {A: 1}\n  name: \"true\"\n  args: []\n\
+             - env: {B: 2}\n  shell: \"true\"\n",
+        )
+        .unwrap();
+        assert_eq!(
+            items,
+            vec![
+                RunItem::new("true".to_string(), vec![])
+                    .with_id("5")
+                    .with_env("A", "1")
+                    .into(),
+                RunItem::shell("true".to_string()).with_env("B", "2").into(),
+            ]
+        );
+    }
+
+    #[test]
+    fn nested_run() {
+        let nested = List::Silent(
+            SilentList::new(vec![RunItem::new(
+                "sh".to_string(),
+                vec!["-c".to_string(), "test \"$LEVEL\" = inner".to_string()],
+            )])
+            .with_defaults(Defaults::new().with_env("LEVEL", "inner")),
+        );
+        let failing = List::Silent(SilentList::new(vec![RunItem::new(
+            "false".to_string(),
+            vec![],
+        )]));
+
+        let result = PromiscuousList::from_items(vec![
+            nested.clone().into(),
+            RunItem::new(
+                "sh".to_string(),
+                vec!["-c".to_string(), "test \"$LEVEL\" = outer".to_string()],
+            )
+            .into(),
+        ])
+        .with_defaults(Defaults::new().with_env("LEVEL", "outer"))
+        .run()
+        .status()
+        .expect("failed to execute process");
+        assert!(result.success());
+
+        let runner = SilentList::from_items(vec![nested.into(), failing.into()]);
+        let error = runner.run().status().unwrap_err();
+        assert!(matches!(error.root(), Error::NonZeroExit { code: 1, .. }));
+        assert_eq!(
+            error.to_string(),
+            "item #1: item #0: `false` return fail execution state with code: 1"
+        );
+    }
+}
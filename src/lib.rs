@@ -3,11 +3,27 @@
 //!
 //! Commands collect to run items in run list.
 
+pub mod check;
+pub mod condition;
+mod document;
+pub mod error;
+pub mod format;
+pub mod graph_list;
 pub mod interactive_list;
+pub mod parallel_list;
+pub mod pipeline_list;
 pub mod promiscuous_list;
 pub mod silent_list;
 
+pub mod item;
+pub mod plan;
+pub mod report;
+pub mod retry;
 pub mod run;
 pub mod runitem;
 pub mod runner;
 pub mod script;
+pub mod shutdown;
+pub mod vars;
+
+pub use error::Error;
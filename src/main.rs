@@ -1,12 +1,274 @@
+use std::fmt::Display;
+use std::io::{self, Write};
 use std::path::PathBuf;
 use std::process;
+use std::time::Duration;
 
-use clap::{App, Arg};
+use clap::{App, Arg, ArgMatches, SubCommand};
 
-use automatic::run::Run;
-use automatic::script::Script;
+use automatic::check::Check;
+use automatic::error::{failure_code, EXIT_PARSE};
+use automatic::format::Format;
+use automatic::plan::Plan;
+use automatic::report::{Report, ReportFormat};
+use automatic::run::{signal_name, Run};
+use automatic::script::{Script, Targets};
+use automatic::shutdown::Shutdown;
+use automatic::vars::Vars;
+
+/// Exit code for wrong command line arguments
+const EXIT_USAGE: i32 = 64;
+/// Exit code for output which can't be written
+const EXIT_IO: i32 = 74;
+
+/// Print error to stderr and exit with `code`
+fn exit<E: Display>(error: E, code: i32) -> ! {
+    eprintln!("{}", error);
+    process::exit(code)
+}
+
+/// Print captured output of failed items, stdout of items goes to stderr
+/// if stdout is taken by report
+fn print_failed(report: &Report, stdout_taken: bool) {
+    for item in report.failed() {
+        if item.stdout.is_empty() && item.stderr.is_empty() {
+            continue;
+        }
+        let ignored = if item.ignored {
+            " (failure ignored)"
+        } else {
+            ""
+        };
+        eprintln!("Output of `{}`{}:", item.command(), ignored);
+        if stdout_taken {
+            io::stderr().write_all(&item.stdout).unwrap_or(());
+        } else {
+            io::stdout().write_all(&item.stdout).unwrap_or(());
+        }
+        io::stderr().write_all(&item.stderr).unwrap_or(());
+    }
+}
+
+/// Parse `NAME=VALUE` overrides of script variables
+fn parse_vars<'a, I: Iterator<Item = &'a str>>(values: I) -> Result<Vars, String> {
+    let mut vars = Vars::new();
+    for value in values {
+        match value.split_once('=') {
+            Some((name, val)) if !name.is_empty() => vars.set(name, val),
+            _ => return Err(format!("wrong variable `{}`, expected NAME=VALUE", value)),
+        }
+    }
+    Ok(vars)
+}
+
+/// Print names of targets with their descriptions, default one is marked
+fn print_targets(targets: &Targets) {
+    let width = targets
+        .iter()
+        .map(|(name, _)| name.len())
+        .max()
+        .unwrap_or(0);
+    for (name, target) in targets.iter() {
+        let mut description = target.description().unwrap_or("").to_string();
+        if targets.default_target() == Some(name) {
+            description.push_str(" (default)");
+        }
+        println!(
+            "{:width$}  {}",
+            name,
+            description.trim_start(),
+            width = width
+        );
+    }
+}
+
+/// Print items skipped because of their conditions, to stderr if stdout is
+/// taken by report
+fn print_skipped(report: &Report, stdout_taken: bool) {
+    for item in report.skipped() {
+        if stdout_taken {
+            eprintln!("Skipped `{}`", item.command());
+        } else {
+            println!("Skipped `{}`", item.command());
+        }
+    }
+}
+
+/// Script from `--eval` content or `--run` path, `-` path is stdin
+fn script(matches: &ArgMatches) -> Option<Script> {
+    if let Some(content) = matches.value_of("eval") {
+        return Some(Script::from_str(content));
+    }
+    match matches.value_of("script")? {
+        "-" => match Script::from_reader(io::stdin()) {
+            Ok(script) => Some(script),
+            Err(e) => exit(&e, e.exit_code()),
+        },
+        path => Some(Script::new(PathBuf::from(path))),
+    }
+}
+
+/// Report goes to stdout: `--report` is set without `--report-file`
+fn report_to_stdout(matches: &ArgMatches) -> bool {
+    matches.is_present("report") && !matches.is_present("report-file")
+}
+
+/// Stdout for report, stdout of automatic is redirected to stderr so items
+/// which inherit it don't mix their output into report
+#[cfg(unix)]
+fn take_stdout() -> Box<dyn Write> {
+    use std::fs::File;
+    use std::os::unix::io::FromRawFd;
+
+    io::stdout().flush().unwrap_or(());
+    let fd = unsafe { libc::dup(libc::STDOUT_FILENO) };
+    if fd < 0 || unsafe { libc::dup2(libc::STDERR_FILENO, libc::STDOUT_FILENO) } < 0 {
+        exit(
+            format!(
+                "fail to redirect stdout with error: {}",
+                io::Error::last_os_error()
+            ),
+            EXIT_IO,
+        );
+    }
+    Box::new(unsafe { File::from_raw_fd(fd) })
+}
+
+#[cfg(not(unix))]
+fn take_stdout() -> Box<dyn Write> {
+    Box::new(io::stdout())
+}
+
+/// Write report in format of `--report` to `--report-file` or `stdout`
+fn write_report(matches: &ArgMatches, report: &Report, name: &str, stdout: Option<Box<dyn Write>>) {
+    let format: ReportFormat = match matches.value_of("report").map(str::parse) {
+        Some(Ok(format)) => format,
+        Some(Err(e)) => exit(e, EXIT_USAGE),
+        None => return,
+    };
+    let encoded = report.encode(format, name);
+    match (matches.value_of("report-file"), stdout) {
+        (Some(path), _) => {
+            if let Err(e) = std::fs::write(path, encoded) {
+                exit(
+                    format!("fail to write report {} with error: {}", path, e),
+                    EXIT_IO,
+                );
+            }
+        }
+        (None, Some(mut stdout)) => {
+            if let Err(e) = stdout
+                .write_all(encoded.as_bytes())
+                .and_then(|_| stdout.flush())
+            {
+                exit(format!("fail to write report with error: {}", e), EXIT_IO);
+            }
+        }
+        (None, None) => print!("{}", encoded),
+    }
+}
+
+/// Format from `name` argument, format detected by extension if not set
+fn format_arg(matches: &ArgMatches, name: &str) -> Option<Format> {
+    let format = matches.value_of(name)?;
+    match format.parse() {
+        Ok(format) => Some(format),
+        Err(e) => exit(e, EXIT_USAGE),
+    }
+}
+
+/// Signal handlers with grace period of `--grace`
+fn shutdown(matches: &ArgMatches) -> Shutdown {
+    let shutdown = match Shutdown::install() {
+        Ok(shutdown) => shutdown,
+        Err(e) => exit(
+            format!("fail to install signal handlers with error: {}", e),
+            EXIT_IO,
+        ),
+    };
+    let grace = matches.value_of("grace").map(|grace| {
+        grace
+            .parse()
+            .ok()
+            .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+    });
+    match grace {
+        Some(Some(grace)) => shutdown.with_grace(grace),
+        Some(None) => exit(
+            "wrong grace period, expected non-negative number of seconds",
+            EXIT_USAGE,
+        ),
+        None => shutdown,
+    }
+}
+
+/// Rewrite script in other format, print it if output path is not set
+fn convert(matches: &ArgMatches) {
+    let input = PathBuf::from(matches.value_of("input").unwrap_or_default());
+    let mut script = Script::new(input);
+    if let Some(format) = format_arg(matches, "format") {
+        script = script.with_format(format);
+    }
+
+    let output = matches.value_of("output").map(PathBuf::from);
+    let format = format_arg(matches, "to").unwrap_or_else(|| match &output {
+        Some(output) => Format::from_path(output),
+        None => Format::default(),
+    });
+
+    let encoded = match script.convert(format) {
+        Ok(encoded) => encoded,
+        Err(e) => exit(&e, e.exit_code()),
+    };
+    match output {
+        Some(output) => {
+            if let Err(e) = std::fs::write(&output, encoded) {
+                exit(
+                    format!("fail to write {} with error: {}", output.display(), e),
+                    EXIT_IO,
+                );
+            }
+        }
+        None => print!("{}", encoded),
+    }
+}
+
+/// Print problems of script, exit with error if there are any
+fn check(matches: &ArgMatches) {
+    let vars = match parse_vars(matches.values_of("set").into_iter().flatten()) {
+        Ok(vars) => vars,
+        Err(e) => exit(e, EXIT_USAGE),
+    };
+    let mut script = match script(matches) {
+        Some(script) => script.with_vars(vars),
+        None => return,
+    };
+    if let Some(format) = format_arg(matches, "format") {
+        script = script.with_format(format);
+    }
+
+    let check = Check::new(&script);
+    print!("{}", check);
+    if !check.is_ok() {
+        process::exit(EXIT_PARSE);
+    }
+}
 
 fn main() {
+    let format = Arg::with_name("format")
+        .long("format")
+        .value_name("FORMAT")
+        .help("Format of script: yaml, json or toml, detected by extension if not set")
+        .takes_value(true);
+
+    let set = Arg::with_name("set")
+        .long("set")
+        .value_name("NAME=VALUE")
+        .help("Override script variable")
+        .takes_value(true)
+        .multiple(true)
+        .number_of_values(1);
+
     let matches = App::new(env!("CARGO_PKG_NAME"))
         .version(env!("CARGO_PKG_VERSION"))
         .author(env!("CARGO_PKG_AUTHORS"))
@@ -16,32 +278,210 @@ fn main() {
                 .short("r")
                 .long("run")
                 .value_name("SCRIPT NAME")
-                .help("Script to run")
+                .help("Script to run, `-` reads script from stdin")
                 .takes_value(true),
         )
-        .get_matches();
+        .arg(
+            Arg::with_name("eval")
+                .short("e")
+                .long("eval")
+                .value_name("SCRIPT")
+                .help("Content of script to run")
+                .takes_value(true)
+                .allow_hyphen_values(true)
+                .conflicts_with("script"),
+        )
+        .arg(set.clone())
+        .arg(
+            Arg::with_name("target")
+                .value_name("TARGET")
+                .help("Target of script to run, default target if not set")
+                .index(1),
+        )
+        .arg(
+            Arg::with_name("list-targets")
+                .long("list-targets")
+                .help("Print targets of script with their descriptions"),
+        )
+        .arg(
+            Arg::with_name("dry-run")
+                .long("dry-run")
+                .help("Print commands of script without running them"),
+        )
+        .arg(
+            Arg::with_name("report")
+                .long("report")
+                .value_name("FORMAT")
+                .help("Print report of run items: json or junit")
+                .takes_value(true)
+                .possible_values(&["json", "junit"]),
+        )
+        .arg(
+            Arg::with_name("report-file")
+                .long("report-file")
+                .value_name("PATH")
+                .help("Write report to file instead of stdout")
+                .takes_value(true)
+                .requires("report"),
+        )
+        .arg(
+            Arg::with_name("grace")
+                .long("grace")
+                .value_name("SECONDS")
+                .help("Time between termination request and kill of interrupted or timed out items")
+                .takes_value(true),
+        )
+        .arg(format.clone())
+        .subcommand(
+            SubCommand::with_name("convert")
+                .about("Rewrite script in other format")
+                .arg(
+                    Arg::with_name("input")
+                        .value_name("INPUT")
+                        .help("Script to convert")
+                        .required(true)
+                        .index(1),
+                )
+                .arg(
+                    Arg::with_name("output")
+                        .value_name("OUTPUT")
+                        .help("Path of converted script, printed if not set")
+                        .index(2),
+                )
+                .arg(format.clone())
+                .arg(
+                    Arg::with_name("to")
+                        .long("to")
+                        .value_name("FORMAT")
+                        .help("Format of converted script, detected by extension of output if not set")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("schema").about("Print JSON Schema of script file"),
+        )
+        .subcommand(
+            SubCommand::with_name("check")
+                .about("Report problems of script, exit with error if there are any")
+                .arg(
+                    Arg::with_name("script")
+                        .value_name("SCRIPT")
+                        .help("Script to check, `-` reads script from stdin")
+                        .required(true)
+                        .index(1),
+                )
+                .arg(format)
+                .arg(set),
+        )
+        .get_matches_safe()
+        .unwrap_or_else(|e| match e.use_stderr() {
+            true => exit(e, EXIT_USAGE),
+            false => e.exit(),
+        });
 
-    if let Some(script_name) = matches.value_of("script") {
-        let script = Script::new(PathBuf::from(script_name));
+    if let Some(matches) = matches.subcommand_matches("convert") {
+        convert(matches);
+        return;
+    }
+    if matches.subcommand_matches("schema").is_some() {
+        let schema = serde_json::to_string_pretty(&Script::schema()).unwrap_or_default();
+        println!("{}", schema);
+        return;
+    }
+    if let Some(matches) = matches.subcommand_matches("check") {
+        check(matches);
+        return;
+    }
 
-        let runner = script.parse().unwrap();
+    if let Some(script) = script(&matches) {
+        let vars = match parse_vars(matches.values_of("set").into_iter().flatten()) {
+            Ok(vars) => vars,
+            Err(e) => exit(e, EXIT_USAGE),
+        };
+        let mut script = script.with_vars(vars);
+        if let Some(format) = format_arg(&matches, "format") {
+            script = script.with_format(format);
+        }
 
-        let result = match runner.run().status() {
+        if matches.is_present("list-targets") {
+            match script.targets() {
+                Ok(targets) => print_targets(&targets),
+                Err(e) => exit(&e, e.exit_code()),
+            }
+            return;
+        }
+
+        if let Some(target) = matches.value_of("target") {
+            script = script.with_target(target);
+        }
+
+        let runner = match script.parse() {
+            Ok(runner) => runner,
+            Err(e) => exit(&e, e.exit_code()),
+        };
+
+        let cleanup = match script.cleanup() {
+            Ok(cleanup) => cleanup,
+            Err(e) => exit(&e, e.exit_code()),
+        };
+
+        if matches.is_present("dry-run") {
+            print!("{}", Plan::new(&runner));
+            let cleanup = Plan::new(&cleanup);
+            if cleanup.items().next().is_some() {
+                print!("Cleanup:\n{}", cleanup);
+            }
+            return;
+        }
+
+        let shutdown = shutdown(&matches);
+        let stdout_taken = report_to_stdout(&matches);
+        let stdout = if stdout_taken {
+            Some(take_stdout())
+        } else {
+            None
+        };
+        let status = shutdown.run(&runner);
+        let cleanup_status = cleanup.run();
+        let mut report = status.report();
+        report.extend(cleanup_status.report());
+        write_report(
+            &matches,
+            &report,
+            &script.path().display().to_string(),
+            stdout,
+        );
+        if let (Some(signal), Some(code)) = (shutdown.signal(), shutdown.exit_code()) {
+            print_failed(&report, stdout_taken);
+            exit(format!("Run interrupted by {}", signal_name(signal)), code);
+        }
+        let result = match status.status() {
             Err(e) => {
-                println!("Failed to run script with error: {}", e);
-                process::exit(1);
+                print_failed(&report, stdout_taken);
+                exit(
+                    format!("Failed to run script with error: {}", e),
+                    e.exit_code(),
+                );
             }
             Ok(ok) => ok,
         };
+        print_skipped(&report, stdout_taken);
+        print_failed(&report, stdout_taken);
 
         if !result.success() {
-            println!(
-                "Unsucessful run with error code: {}",
-                result.code().unwrap_or(0)
+            let code = result.code().unwrap_or(1);
+            exit(
+                format!("Unsuccessful run with error code: {}", code),
+                failure_code(code),
             );
         };
+        if let Err(e) = cleanup_status.status() {
+            exit(
+                format!("Failed to run cleanup with error: {}", e),
+                e.exit_code(),
+            );
+        }
     } else {
-        println!("No provided script path");
-        process::exit(1);
+        exit("No provided script path", EXIT_USAGE);
     }
 }
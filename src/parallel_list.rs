@@ -0,0 +1,298 @@
+//! Parallel run items, no output, runs up to `max_jobs` items at once and
+//! reports every failed item
+//!
+//! # Example
+//!```
+//!use automatic::parallel_list::{ParallelList, Policy};
+//!use automatic::runitem::RunItem;
+//!use automatic::run::Run;
+//!let items = vec![
+//!    RunItem::new("true".to_string(), vec![]),
+//!    RunItem::new("true".to_string(), vec![]),
+//!    RunItem::new("true".to_string(), vec![]),
+//!];
+//!
+//!let result = ParallelList::new(items)
+//!    .with_max_jobs(2)
+//!    .with_policy(Policy::FailFast)
+//!    .run()
+//!    .status()
+//!    .expect("failed to execute process");
+//!assert!(result.success());
+//!```
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::error::Error;
+use crate::item::{Context, Item, RunIn};
+use crate::report::Report;
+use crate::run::{check_exit, ExitStatus, Run, RunStatus};
+use crate::runitem::{Defaults, RunItem};
+use crate::runner::Cancel;
+use crate::vars::Vars;
+
+/// Behaviour of parallel list when one of items fails
+#[derive(Debug, PartialEq, Serialize, Deserialize, JsonSchema, Clone, Copy, Default)]
+pub enum Policy {
+    /// Run all items regardless of failures
+    #[default]
+    WaitAll,
+    /// Cancel running items and don't start new ones after first failure
+    FailFast,
+}
+
+fn is_wait_all(policy: &Policy) -> bool {
+    *policy == Policy::WaitAll
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, JsonSchema, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ParallelList {
+    items: Vec<Item>,
+    #[serde(default, skip_serializing_if = "Defaults::is_empty")]
+    defaults: Defaults,
+    /// Maximum count of simultaneously running items, count of CPUs if unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    max_jobs: Option<usize>,
+    #[serde(default, skip_serializing_if = "is_wait_all")]
+    policy: Policy,
+}
+
+impl ParallelList {
+    pub fn new(items: Vec<RunItem>) -> Self {
+        Self::from_items(items.into_iter().map(Item::from).collect())
+    }
+
+    pub fn from_items(items: Vec<Item>) -> Self {
+        Self {
+            items,
+            defaults: Defaults::new(),
+            max_jobs: None,
+            policy: Policy::WaitAll,
+        }
+    }
+
+    pub fn with_defaults(mut self, defaults: Defaults) -> Self {
+        self.defaults = defaults;
+        self
+    }
+
+    pub fn items(&self) -> &[Item] {
+        &self.items
+    }
+
+    pub fn defaults(&self) -> &Defaults {
+        &self.defaults
+    }
+
+    pub(crate) fn items_mut(&mut self) -> &mut Vec<Item> {
+        &mut self.items
+    }
+
+    pub(crate) fn interpolate(&mut self, vars: &Vars) -> Result<(), Error> {
+        self.defaults.interpolate(vars)?;
+        for item in self.items.iter_mut() {
+            item.interpolate(vars)?;
+        }
+        Ok(())
+    }
+
+    pub fn with_max_jobs(mut self, max_jobs: usize) -> Self {
+        self.max_jobs = Some(max_jobs);
+        self
+    }
+
+    pub fn with_policy(mut self, policy: Policy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    fn jobs(&self) -> usize {
+        let jobs = match self.max_jobs {
+            Some(jobs) => jobs,
+            None => thread::available_parallelism().map_or(1, |jobs| jobs.get()),
+        };
+        jobs.clamp(1, self.items.len().max(1))
+    }
+
+    fn run_internal(&self, ctx: &Context, report: &mut Report) -> Result<(), Error> {
+        let cancel = Cancel::new();
+        let ctx = ctx.with_cancel(cancel.clone());
+        let next = AtomicUsize::new(0);
+        let failures = Mutex::new(vec![]);
+        let reports = Mutex::new(vec![]);
+
+        thread::scope(|scope| {
+            for _ in 0..self.jobs() {
+                scope.spawn(|| loop {
+                    if cancel.is_raised() {
+                        break;
+                    }
+                    let index = next.fetch_add(1, Ordering::SeqCst);
+                    let item = match self.items.get(index) {
+                        Some(item) => item,
+                        None => break,
+                    };
+
+                    let status = item.run_in(&ctx, |r| r);
+                    reports.lock().unwrap().push((index, status.report()));
+                    if let Err(e) = check_exit(item, &*status) {
+                        if self.policy == Policy::FailFast {
+                            cancel.raise();
+                        }
+                        failures.lock().unwrap().push((index, e));
+                    }
+                });
+            }
+        });
+
+        let mut reports = reports.into_inner().unwrap();
+        reports.sort_by_key(|(index, _)| *index);
+        for (_, item_report) in reports {
+            report.extend(item_report);
+        }
+
+        let mut failures = failures.into_inner().unwrap();
+        if failures.is_empty() {
+            return Ok(());
+        }
+
+        failures.sort_by_key(|(index, _)| *index);
+        Err(Error::Parallel {
+            total: self.items.len(),
+            failures: failures
+                .into_iter()
+                .map(|(index, e)| Error::Item {
+                    index,
+                    source: Box::new(e),
+                })
+                .collect(),
+        })
+    }
+}
+
+struct StatusHelper {}
+
+impl ExitStatus for StatusHelper {}
+
+struct RunItemStatus {
+    status: Result<(), Error>,
+    report: Report,
+}
+
+impl RunStatus for RunItemStatus {
+    fn status(&self) -> Result<Box<dyn ExitStatus>, Error> {
+        match &self.status {
+            Ok(_) => Ok(Box::new(StatusHelper {})),
+            Err(e) => Err(e.clone()),
+        }
+    }
+
+    fn report(&self) -> Report {
+        self.report.clone()
+    }
+}
+
+impl RunIn for ParallelList {
+    fn run_in(&self, ctx: &Context) -> Box<dyn RunStatus> {
+        let mut report = Report::new();
+        let status = self.run_internal(&ctx.nested(&self.defaults), &mut report);
+        Box::new(RunItemStatus { status, report })
+    }
+}
+
+impl Run for ParallelList {
+    fn run(&self) -> Box<dyn RunStatus> {
+        self.run_in(&Context::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use crate::runitem::RunItem;
+
+    use super::*;
+
+    fn sleep(secs: &str) -> RunItem {
+        RunItem::new("sleep".to_string(), vec![secs.to_string()])
+    }
+
+    #[test]
+    fn success_run() {
+        let items = vec![sleep("0.2"), sleep("0.2"), sleep("0.2"), sleep("0.2")];
+
+        let start = Instant::now();
+        let result = ParallelList::new(items)
+            .with_max_jobs(4)
+            .run()
+            .status()
+            .expect("failed to execute process");
+        assert!(result.success());
+        assert!(start.elapsed() < Duration::from_millis(700));
+    }
+
+    #[test]
+    fn bounded_run() {
+        let items = vec![sleep("0.2"), sleep("0.2"), sleep("0.2")];
+
+        let start = Instant::now();
+        let result = ParallelList::new(items)
+            .with_max_jobs(1)
+            .run()
+            .status()
+            .expect("failed to execute process");
+        assert!(result.success());
+        assert!(start.elapsed() >= Duration::from_millis(600));
+    }
+
+    #[test]
+    fn wait_all_run() {
+        let items = vec![
+            RunItem::new("false".to_string(), vec![]),
+            RunItem::new("true".to_string(), vec![]),
+            RunItem::new("bla bla".to_string(), vec![]),
+        ];
+
+        let runner = ParallelList::new(items).with_max_jobs(2);
+        assert_eq!(
+            runner.run().status().unwrap_err().to_string(),
+            "2 of 3 parallel items failed: \
+             item #0: `false` return fail execution state with code: 1; \
+             item #2: fail to run `bla bla`: No such file or directory (os error 2)"
+        );
+    }
+
+    #[test]
+    fn fail_fast_run() {
+        let items = vec![
+            sleep("10"),
+            RunItem::new("false".to_string(), vec![]),
+            sleep("10"),
+        ];
+
+        let start = Instant::now();
+        let runner = ParallelList::new(items)
+            .with_max_jobs(2)
+            .with_policy(Policy::FailFast);
+        match runner.run().status().unwrap_err() {
+            Error::Parallel { total, failures } => {
+                assert_eq!(total, 3);
+                assert_eq!(failures.len(), 2);
+                assert!(matches!(failures[0].root(), Error::Cancelled { .. }));
+                assert!(matches!(
+                    failures[1].root(),
+                    Error::NonZeroExit { code: 1, .. }
+                ));
+            }
+            e => panic!("unexpected error: {}", e),
+        }
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+}
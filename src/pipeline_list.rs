@@ -0,0 +1,263 @@
+//! Pipeline of run items, stdout of every item goes to stdin of the next one,
+//! fails if any item fails like shell pipeline with `pipefail` option, items
+//! with not holding conditions are left out of pipeline
+//!
+//! # Example
+//!```
+//!use automatic::pipeline_list::PipelineList;
+//!use automatic::runitem::RunItem;
+//!use automatic::run::Run;
+//!let items = vec![
+//!    RunItem::new("echo".to_string(), vec!["b\na".to_string()]),
+//!    RunItem::new("sort".to_string(), vec![]),
+//!];
+//!
+//!let status = PipelineList::new(items).run();
+//!let result = status.status().expect("failed to execute process");
+//!assert!(result.success());
+//!
+//!let report = status.report();
+//!assert_eq!(report.items[1].stdout, b"a\nb\n");
+//!```
+
+use std::time::Instant;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::error::Error;
+use crate::item::{Context, RunIn};
+use crate::report::Report;
+use crate::run::{check_exit, ExitStatus, Run, RunStatus};
+use crate::runitem::{Defaults, RunItem};
+use crate::runner::{Runned, Stdio};
+use crate::vars::Vars;
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, JsonSchema, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct PipelineList {
+    items: Vec<RunItem>,
+    #[serde(default, skip_serializing_if = "Defaults::is_empty")]
+    defaults: Defaults,
+}
+
+impl PipelineList {
+    pub fn new(items: Vec<RunItem>) -> Self {
+        Self {
+            items,
+            defaults: Defaults::new(),
+        }
+    }
+
+    pub fn with_defaults(mut self, defaults: Defaults) -> Self {
+        self.defaults = defaults;
+        self
+    }
+
+    pub fn items(&self) -> &[RunItem] {
+        &self.items
+    }
+
+    pub fn defaults(&self) -> &Defaults {
+        &self.defaults
+    }
+
+    pub(crate) fn interpolate(&mut self, vars: &Vars) -> Result<(), Error> {
+        self.defaults.interpolate(vars)?;
+        for item in self.items.iter_mut() {
+            item.interpolate(vars)?;
+        }
+        Ok(())
+    }
+
+    /// Start every stage of pipeline, runners are dropped right after spawn
+    /// to close their copies of pipe ends
+    fn spawn(&self, items: &[RunItem], ctx: &Context) -> Vec<Result<Runned, Error>> {
+        let mut stages = vec![];
+        let mut stdin = None;
+        for (index, item) in items.iter().enumerate() {
+            let mut runner = item.runner();
+            ctx.configure(&mut runner);
+            runner.set_stdin(stdin.take().unwrap_or_else(Stdio::null));
+
+            if index + 1 < items.len() {
+                match os_pipe::pipe() {
+                    Ok((reader, writer)) => {
+                        runner.set_stdout(writer);
+                        stdin = Some(Stdio::from(reader));
+                    }
+                    Err(e) => {
+                        stages.push(Err(item.run_error(e)));
+                        continue;
+                    }
+                }
+            }
+
+            stages.push(runner.spawn().map_err(|e| item.run_error(e)));
+        }
+        stages
+    }
+
+    fn run_internal(&self, ctx: &Context, report: &mut Report) -> Result<(), Error> {
+        let items: Vec<RunItem> = self
+            .items
+            .iter()
+            .map(|item| item.inherit(&ctx.defaults))
+            .collect();
+
+        let start = Instant::now();
+        let conditions: Vec<Result<bool, Error>> = items.iter().map(RunItem::should_run).collect();
+        let active: Vec<RunItem> = items
+            .iter()
+            .zip(conditions.iter())
+            .filter(|(_, condition)| matches!(condition, Ok(true)))
+            .map(|(item, _)| item.clone())
+            .collect();
+
+        let mut stages = self.spawn(&active, ctx);
+        for stage in stages.iter_mut().flatten() {
+            stage.start_capture();
+        }
+
+        let mut stages = stages.into_iter();
+        let mut failure = None;
+        for (index, (item, condition)) in items.iter().zip(conditions).enumerate() {
+            let status = match condition {
+                Ok(false) => item.skipped(),
+                Ok(true) => {
+                    let attempt = match stages.next().unwrap() {
+                        Ok(mut stage) => item.attempt(stage.wait_with_output()),
+                        Err(e) => e.into(),
+                    };
+                    item.status(vec![attempt], start.elapsed())
+                }
+                Err(e) => item.status(vec![e.into()], start.elapsed()),
+            };
+            report.extend(status.report());
+            if let Err(e) = check_exit(item, &*status) {
+                failure = Some(Error::Item {
+                    index,
+                    source: Box::new(e),
+                });
+            }
+        }
+
+        match failure {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+}
+
+struct StatusHelper {}
+
+impl ExitStatus for StatusHelper {}
+
+struct RunItemStatus {
+    status: Result<(), Error>,
+    report: Report,
+}
+
+impl RunStatus for RunItemStatus {
+    fn status(&self) -> Result<Box<dyn ExitStatus>, Error> {
+        match &self.status {
+            Ok(_) => Ok(Box::new(StatusHelper {})),
+            Err(e) => Err(e.clone()),
+        }
+    }
+
+    fn report(&self) -> Report {
+        self.report.clone()
+    }
+}
+
+impl RunIn for PipelineList {
+    fn run_in(&self, ctx: &Context) -> Box<dyn RunStatus> {
+        let mut report = Report::new();
+        let status = self.run_internal(&ctx.nested(&self.defaults), &mut report);
+        Box::new(RunItemStatus { status, report })
+    }
+}
+
+impl Run for PipelineList {
+    fn run(&self) -> Box<dyn RunStatus> {
+        self.run_in(&Context::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::condition::Condition;
+    use crate::runitem::RunItem;
+
+    use super::*;
+
+    fn sh(script: &str) -> RunItem {
+        RunItem::new("sh".to_string(), vec!["-c".to_string(), script.to_string()])
+    }
+
+    #[test]
+    fn success_run() {
+        let items = vec![
+            sh("echo 123456"),
+            RunItem::new("cat".to_string(), vec![]),
+            RunItem::new("rev".to_string(), vec![]),
+        ];
+
+        let status = PipelineList::new(items).run();
+        let result = status.status().expect("failed to execute process");
+        assert!(result.success());
+
+        let report = status.report();
+        assert_eq!(report.items.len(), 3);
+        assert!(report.items[0].stdout.is_empty());
+        assert_eq!(report.items[2].stdout, b"654321\n");
+    }
+
+    #[test]
+    fn pipefail_run() {
+        let items = vec![
+            sh("exit 3"),
+            sh("cat; exit 4"),
+            RunItem::new("cat".to_string(), vec![]),
+        ];
+
+        let status = PipelineList::new(items).run();
+        let error = status.status().unwrap_err();
+        assert!(matches!(error, Error::Item { index: 1, .. }));
+        assert!(matches!(error.root(), Error::NonZeroExit { code: 4, .. }));
+
+        let codes: Vec<Option<i32>> = status.report().items.iter().map(|i| i.code).collect();
+        assert_eq!(codes, vec![Some(3), Some(4), Some(0)]);
+    }
+
+    #[test]
+    fn fail_run() {
+        let items = vec![
+            sh("echo 1"),
+            RunItem::new("bla bla".to_string(), vec![]),
+            RunItem::new("cat".to_string(), vec![]),
+        ];
+
+        let status = PipelineList::new(items).run();
+        let error = status.status().unwrap_err();
+        assert!(matches!(error.root(), Error::Spawn { .. }));
+        assert_eq!(status.report().items.len(), 3);
+    }
+
+    #[test]
+    fn skip_run() {
+        let items = vec![
+            sh("echo 123"),
+            sh("rev").with_when(Condition::new().with_os("no such os")),
+            RunItem::new("cat".to_string(), vec![]),
+        ];
+
+        let status = PipelineList::new(items).run();
+        assert!(status.status().unwrap().success());
+
+        let report = status.report();
+        assert!(report.items[1].skipped);
+        assert_eq!(report.items[2].stdout, b"123\n");
+    }
+}
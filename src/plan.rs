@@ -0,0 +1,188 @@
+//! Resolved plan of script run: commands with applied defaults of their lists
+//! in run order, collected without spawning anything
+//!
+//! # Example
+//!```
+//!use std::path::PathBuf;
+//!
+//!use automatic::plan::Plan;
+//!use automatic::script::Script;
+//!
+//!let list = Script::new(PathBuf::from("tests/test_script_for_run_env.yaml"))
+//!    .parse()
+//!    .unwrap();
+//!
+//!let plan = Plan::new(&list);
+//!let items: Vec<_> = plan.items().collect();
+//!
+//!assert_eq!(items.len(), 4);
+//!assert_eq!(items[0].cwd(), Some(PathBuf::from("tests").as_path()));
+//!assert_eq!(items[1].env()["AUTOMATIC_MODE"], "item");
+//!```
+
+use std::fmt;
+
+use crate::runitem::{Defaults, RunItem};
+use crate::script::List;
+
+/// Visitor of list tree, see [`List::accept`]
+pub trait Visitor {
+    /// Called before items of list, `defaults` include defaults of outer lists
+    fn enter_list(&mut self, _list: &List, _defaults: &Defaults) {}
+
+    /// Called after items of list
+    fn leave_list(&mut self, _list: &List) {}
+
+    /// Called for every run item with applied defaults of its lists
+    fn visit_item(&mut self, item: &RunItem);
+}
+
+/// Step of plan, `depth` is count of lists which contain the step
+#[derive(Debug, Clone, PartialEq)]
+pub enum Step {
+    List {
+        depth: usize,
+        mode: String,
+        defaults: Defaults,
+    },
+    Run {
+        depth: usize,
+        item: RunItem,
+    },
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Plan {
+    pub steps: Vec<Step>,
+    depth: usize,
+}
+
+impl Plan {
+    pub fn new(list: &List) -> Self {
+        let mut plan = Self::default();
+        list.accept(&mut plan);
+        plan
+    }
+
+    /// Run items of plan in run order
+    pub fn items(&self) -> impl Iterator<Item = &RunItem> {
+        self.steps.iter().filter_map(|step| match step {
+            Step::Run { item, .. } => Some(item),
+            Step::List { .. } => None,
+        })
+    }
+}
+
+impl Visitor for Plan {
+    fn enter_list(&mut self, list: &List, defaults: &Defaults) {
+        self.steps.push(Step::List {
+            depth: self.depth,
+            mode: list.to_string(),
+            defaults: defaults.clone(),
+        });
+        self.depth += 1;
+    }
+
+    fn leave_list(&mut self, _list: &List) {
+        self.depth -= 1;
+    }
+
+    fn visit_item(&mut self, item: &RunItem) {
+        self.steps.push(Step::Run {
+            depth: self.depth,
+            item: item.clone(),
+        });
+    }
+}
+
+/// Changes of environment of automatic process made for item
+fn env_diff(item: &RunItem) -> Vec<String> {
+    let mut diff = vec![];
+    if item.env_clear() {
+        diff.push("(cleared)".to_string());
+    }
+    diff.extend(
+        item.env()
+            .iter()
+            .map(|(key, val)| format!("+{}={}", key, val)),
+    );
+    diff.extend(item.env_remove().iter().map(|key| format!("-{}", key)));
+    diff
+}
+
+impl fmt::Display for Plan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for step in self.steps.iter() {
+            match step {
+                Step::List { depth, mode, .. } => {
+                    writeln!(f, "{:indent$}{}", "", mode, indent = depth * 2)?;
+                }
+                Step::Run { depth, item } => {
+                    let indent = depth * 2;
+                    writeln!(f, "{:indent$}{}", "", item, indent = indent)?;
+                    if let Some(cwd) = item.cwd() {
+                        writeln!(f, "{:indent$}  cwd: {}", "", cwd.display(), indent = indent)?;
+                    }
+                    let diff = env_diff(item);
+                    if !diff.is_empty() {
+                        writeln!(
+                            f,
+                            "{:indent$}  env: {}",
+                            "",
+                            diff.join(" "),
+                            indent = indent
+                        )?;
+                    }
+                    if let Some(timeout) = item.timeout() {
+                        let secs = timeout.as_secs_f64();
+                        writeln!(f, "{:indent$}  timeout: {}s", "", secs, indent = indent)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::item::Item;
+    use crate::pipeline_list::PipelineList;
+    use crate::silent_list::SilentList;
+
+    use super::*;
+
+    #[test]
+    fn plan() {
+        let pipeline = PipelineList::new(vec![
+            RunItem::new("echo".to_string(), vec!["a".to_string()]).with_cwd("src"),
+            RunItem::new("wc".to_string(), vec![]).with_env_remove("B"),
+        ])
+        .with_defaults(Defaults::new().with_env("B", "2"));
+        let list = List::Silent(
+            SilentList::from_items(vec![
+                Item::Run(RunItem::new("true".to_string(), vec![]).with_env_clear(true)),
+                Item::List(List::Pipeline(pipeline)),
+            ])
+            .with_defaults(Defaults::new().with_env("A", "1").with_cwd("/tmp")),
+        );
+
+        let plan = Plan::new(&list);
+
+        assert_eq!(plan.items().count(), 3);
+        assert_eq!(
+            plan.to_string(),
+            "Silent list\
+             \n  true\
+             \n    cwd: /tmp\
+             \n    env: (cleared) +A=1\
+             \n  Pipeline list\
+             \n    echo a\
+             \n      cwd: /tmp/src\
+             \n      env: +A=1 +B=2\
+             \n    wc\
+             \n      cwd: /tmp\
+             \n      env: +A=1 -B\n"
+        );
+    }
+}
@@ -18,26 +18,69 @@
 //!assert!(result.success());
 //!```
 
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use crate::run::{ExitStatus, Run, RunStatus};
-use crate::runitem::RunItem;
+use crate::error::Error;
+use crate::item::{Context, Item, RunIn};
+use crate::report::Report;
+use crate::run::{check_run, ExitStatus, Run, RunStatus};
+use crate::runitem::{Defaults, RunItem};
+use crate::vars::Vars;
 
-#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+#[derive(Debug, PartialEq, Serialize, Deserialize, JsonSchema, Clone)]
+#[serde(deny_unknown_fields)]
 pub struct PromiscuousList {
-    items: Vec<RunItem>,
+    items: Vec<Item>,
+    #[serde(default, skip_serializing_if = "Defaults::is_empty")]
+    defaults: Defaults,
 }
 
 impl PromiscuousList {
     pub fn new(items: Vec<RunItem>) -> Self {
-        Self { items }
+        Self::from_items(items.into_iter().map(Item::from).collect())
     }
 
-    fn run_internal(&self) -> Result<(), String> {
-        for item in self.items.iter() {
-            if let Err(e) = item.run().status() {
-                return Err(e.to_string());
-            }
+    pub fn from_items(items: Vec<Item>) -> Self {
+        Self {
+            items,
+            defaults: Defaults::new(),
+        }
+    }
+
+    pub fn with_defaults(mut self, defaults: Defaults) -> Self {
+        self.defaults = defaults;
+        self
+    }
+
+    pub fn items(&self) -> &[Item] {
+        &self.items
+    }
+
+    pub fn defaults(&self) -> &Defaults {
+        &self.defaults
+    }
+
+    pub(crate) fn items_mut(&mut self) -> &mut Vec<Item> {
+        &mut self.items
+    }
+
+    pub(crate) fn interpolate(&mut self, vars: &Vars) -> Result<(), Error> {
+        self.defaults.interpolate(vars)?;
+        for item in self.items.iter_mut() {
+            item.interpolate(vars)?;
+        }
+        Ok(())
+    }
+
+    fn run_internal(&self, ctx: &Context, report: &mut Report) -> Result<(), Error> {
+        for (index, item) in self.items.iter().enumerate() {
+            let status = item.run_in(ctx, |r| r);
+            report.extend(status.report());
+            check_run(item, &*status).map_err(|e| Error::Item {
+                index,
+                source: Box::new(e),
+            })?;
         }
         Ok(())
     }
@@ -48,23 +91,34 @@ struct StatusHelper {}
 impl ExitStatus for StatusHelper {}
 
 struct RunItemStatus {
-    status: Result<(), String>,
+    status: Result<(), Error>,
+    report: Report,
 }
 
 impl RunStatus for RunItemStatus {
-    fn status(&self) -> Result<Box<dyn ExitStatus>, String> {
+    fn status(&self) -> Result<Box<dyn ExitStatus>, Error> {
         match &self.status {
             Ok(_) => Ok(Box::new(StatusHelper {})),
-            Err(e) => Err(e.to_string()),
+            Err(e) => Err(e.clone()),
         }
     }
+
+    fn report(&self) -> Report {
+        self.report.clone()
+    }
+}
+
+impl RunIn for PromiscuousList {
+    fn run_in(&self, ctx: &Context) -> Box<dyn RunStatus> {
+        let mut report = Report::new();
+        let status = self.run_internal(&ctx.nested(&self.defaults), &mut report);
+        Box::new(RunItemStatus { status, report })
+    }
 }
 
 impl Run for PromiscuousList {
     fn run(&self) -> Box<dyn RunStatus> {
-        Box::new(RunItemStatus {
-            status: self.run_internal(),
-        })
+        self.run_in(&Context::default())
     }
 }
 
@@ -98,9 +152,11 @@ mod tests {
         ];
 
         let runner = PromiscuousList::new(items);
+        let error = runner.run().status().unwrap_err();
+        assert!(matches!(error.root(), Error::Spawn { item, .. } if item == "bla bla"));
         assert_eq!(
-            runner.run().status(),
-            Err("No such file or directory (os error 2)".to_string())
+            error.to_string(),
+            "item #1: fail to run `bla bla`: No such file or directory (os error 2)"
         );
     }
 }
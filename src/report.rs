@@ -0,0 +1,354 @@
+//! Run report with captured output, exit code and duration of every item
+//!
+//! # Example
+//!```
+//!use automatic::runitem::RunItem;
+//!use automatic::silent_list::SilentList;
+//!use automatic::run::Run;
+//!
+//!let items = vec![
+//!    RunItem::new("echo".to_string(), vec!["first".to_string()]),
+//!    RunItem::new("sh".to_string(), vec!["-c".to_string(), "echo second; exit 3".to_string()]),
+//!];
+//!
+//!let report = SilentList::new(items).run().report();
+//!
+//!assert_eq!(report.items.len(), 2);
+//!
+//!let failed: Vec<_> = report.failed().collect();
+//!assert_eq!(failed.len(), 1);
+//!assert_eq!(failed[0].code, Some(3));
+//!assert_eq!(failed[0].stdout, b"second\n");
+//!
+//!let junit = report.to_junit("script.yaml");
+//!assert!(junit.contains("<testsuite name=\"script.yaml\" tests=\"2\" failures=\"1\""));
+//!```
+
+use std::fmt::Write;
+use std::str::FromStr;
+use std::time::Duration;
+
+use serde_json::{json, Value};
+
+use crate::error::Error;
+use crate::run::signal_name;
+
+/// Machine-readable format of report
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ReportFormat {
+    Json,
+    /// JUnit XML, every item is test case
+    Junit,
+}
+
+impl FromStr for ReportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(ReportFormat::Json),
+            "junit" => Ok(ReportFormat::Junit),
+            _ => Err(format!(
+                "unknown report format `{}`, expected json or junit",
+                s
+            )),
+        }
+    }
+}
+
+/// Report of single run item
+#[derive(Debug, Clone, Default)]
+pub struct ItemReport {
+    pub name: String,
+    pub args: Vec<String>,
+    /// Exit code of the last attempt, `None` if item wasn't run to the end or
+    /// was terminated by signal
+    pub code: Option<i32>,
+    /// Signal which terminated the last attempt
+    pub signal: Option<i32>,
+    /// Core dump was written when signal terminated the last attempt
+    pub core_dumped: bool,
+    /// Captured output of the last attempt, empty for not captured output
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+    /// Wall-clock duration of all attempts
+    pub duration: Duration,
+    pub attempts: usize,
+    /// Error of run, `None` if item was run regardless of its exit code
+    pub error: Option<Error>,
+    /// Exit codes treated as success, only zero code if empty
+    pub success_codes: Vec<i32>,
+    /// Item failed but list continued to run because of `ignore_failure`
+    pub ignored: bool,
+    /// Item wasn't run because its conditions don't hold
+    pub skipped: bool,
+}
+
+impl ItemReport {
+    /// Item was run and exited with success code
+    pub fn success(&self) -> bool {
+        match (&self.error, self.code) {
+            (None, Some(code)) if self.success_codes.is_empty() => code == 0,
+            (None, Some(code)) => self.success_codes.contains(&code),
+            _ => false,
+        }
+    }
+
+    /// Command line of item
+    pub fn command(&self) -> String {
+        let mut command = vec![self.name.as_str()];
+        command.extend(self.args.iter().map(String::as_str));
+        command.join(" ")
+    }
+
+    /// Reason of failure, `None` for successful and skipped items
+    fn failure(&self) -> Option<String> {
+        if self.skipped || self.success() {
+            return None;
+        }
+        match (&self.error, self.code, self.signal) {
+            (Some(e), _, _) => Some(e.to_string()),
+            (None, Some(code), _) => Some(format!("exit code {}", code)),
+            (None, None, Some(signal)) if self.core_dumped => Some(format!(
+                "terminated by {} (core dumped)",
+                signal_name(signal)
+            )),
+            (None, None, Some(signal)) => Some(format!("terminated by {}", signal_name(signal))),
+            (None, None, None) => Some("not finished".to_string()),
+        }
+    }
+
+    fn to_json(&self) -> Value {
+        json!({
+            "name": self.name,
+            "args": self.args,
+            "code": self.code,
+            "signal": self.signal.map(signal_name),
+            "core_dumped": self.core_dumped,
+            "duration": self.duration.as_secs_f64(),
+            "attempts": self.attempts,
+            "success": self.success(),
+            "ignored": self.ignored,
+            "skipped": self.skipped,
+            "error": self.failure(),
+            "stdout": String::from_utf8_lossy(&self.stdout),
+            "stderr": String::from_utf8_lossy(&self.stderr),
+        })
+    }
+}
+
+/// Report of items in run order, items of nested lists are included in place
+#[derive(Debug, Clone, Default)]
+pub struct Report {
+    pub items: Vec<ItemReport>,
+}
+
+impl Report {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn extend(&mut self, other: Report) {
+        self.items.extend(other.items);
+    }
+
+    /// Items which failed to run or exited with not success code, including
+    /// items with ignored failure
+    pub fn failed(&self) -> impl Iterator<Item = &ItemReport> {
+        self.items
+            .iter()
+            .filter(|item| !item.skipped && !item.success())
+    }
+
+    /// Items which weren't run because of their conditions
+    pub fn skipped(&self) -> impl Iterator<Item = &ItemReport> {
+        self.items.iter().filter(|item| item.skipped)
+    }
+
+    /// Report in `format`, `name` is name of test suite of JUnit report
+    pub fn encode(&self, format: ReportFormat, name: &str) -> String {
+        match format {
+            ReportFormat::Json => self.to_json(),
+            ReportFormat::Junit => self.to_junit(name),
+        }
+    }
+
+    /// JSON object with array of items, output of items is lossy decoded
+    /// UTF-8
+    pub fn to_json(&self) -> String {
+        let items: Vec<Value> = self.items.iter().map(ItemReport::to_json).collect();
+        let report = json!({
+            "success": self.items.iter().all(|item| item.skipped || item.ignored || item.success()),
+            "items": items,
+        });
+        serde_json::to_string_pretty(&report).unwrap_or_default() + "\n"
+    }
+
+    /// JUnit XML with test suite `name`, failures of items with ignored
+    /// failure are not counted
+    pub fn to_junit(&self, name: &str) -> String {
+        let failures = self
+            .items
+            .iter()
+            .filter(|item| !item.ignored && item.failure().is_some())
+            .count();
+        let skipped = self.skipped().count();
+        let time: f64 = self
+            .items
+            .iter()
+            .map(|item| item.duration.as_secs_f64())
+            .sum();
+
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        let suite = format!(
+            "tests=\"{}\" failures=\"{}\" skipped=\"{}\" time=\"{:.3}\"",
+            self.items.len(),
+            failures,
+            skipped,
+            time
+        );
+        writeln!(xml, "<testsuites {}>", suite).unwrap_or(());
+        writeln!(xml, "  <testsuite name=\"{}\" {}>", escape(name), suite).unwrap_or(());
+        for item in self.items.iter() {
+            let mut body = String::new();
+            if item.skipped {
+                body.push_str("\n      <skipped/>");
+            }
+            if let Some(failure) = item.failure() {
+                let failure = escape(&failure);
+                match item.ignored {
+                    true => write!(
+                        body,
+                        "\n      <properties>\
+                         <property name=\"ignored_failure\" value=\"{}\"/>\
+                         </properties>",
+                        failure
+                    ),
+                    false => write!(body, "\n      <failure message=\"{}\"/>", failure),
+                }
+                .unwrap_or(());
+            }
+            for (tag, output) in [("system-out", &item.stdout), ("system-err", &item.stderr)].iter()
+            {
+                if !output.is_empty() {
+                    let output = escape(&String::from_utf8_lossy(output));
+                    write!(body, "\n      <{tag}>{}</{tag}>", output, tag = tag).unwrap_or(());
+                }
+            }
+
+            write!(
+                xml,
+                "    <testcase name=\"{}\" classname=\"{}\" time=\"{:.3}\"",
+                escape(&item.command()),
+                escape(name),
+                item.duration.as_secs_f64()
+            )
+            .unwrap_or(());
+            match body.is_empty() {
+                true => xml.push_str("/>\n"),
+                false => write!(xml, ">{}\n    </testcase>\n", body).unwrap_or(()),
+            }
+        }
+        xml.push_str("  </testsuite>\n</testsuites>\n");
+        xml
+    }
+}
+
+/// Escape text for XML attribute or element, control characters which XML
+/// doesn't allow are dropped
+fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            '\t' | '\n' | '\r' => escaped.push(c),
+            c if c.is_control() => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report() -> Report {
+        Report {
+            items: vec![
+                ItemReport {
+                    name: "echo".to_string(),
+                    args: vec!["<a & b>".to_string()],
+                    code: Some(0),
+                    stdout: b"<a & b>\n".to_vec(),
+                    duration: Duration::from_millis(1500),
+                    attempts: 1,
+                    ..Default::default()
+                },
+                ItemReport {
+                    name: "false".to_string(),
+                    code: Some(1),
+                    stderr: b"\x1b[31mfailed\x1b[0m\n".to_vec(),
+                    attempts: 2,
+                    ..Default::default()
+                },
+                ItemReport {
+                    name: "true".to_string(),
+                    skipped: true,
+                    ..Default::default()
+                },
+                ItemReport {
+                    name: "sleep".to_string(),
+                    args: vec!["10".to_string()],
+                    signal: Some(9),
+                    ..Default::default()
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn json() {
+        let json: Value = serde_json::from_str(&report().to_json()).unwrap();
+
+        assert_eq!(json["success"], false);
+        assert_eq!(json["items"][0]["args"][0], "<a & b>");
+        assert_eq!(json["items"][0]["duration"], 1.5);
+        assert_eq!(json["items"][0]["error"], Value::Null);
+        assert_eq!(json["items"][1]["code"], 1);
+        assert_eq!(json["items"][1]["attempts"], 2);
+        assert_eq!(json["items"][1]["error"], "exit code 1");
+        assert_eq!(json["items"][2]["skipped"], true);
+        assert_eq!(json["items"][3]["signal"], "SIGKILL");
+        assert_eq!(json["items"][3]["error"], "terminated by SIGKILL");
+    }
+
+    #[test]
+    fn junit() {
+        assert_eq!(
+            report().to_junit("script.yaml"),
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\
+             \n<testsuites tests=\"4\" failures=\"2\" skipped=\"1\" time=\"1.500\">\
+             \n  <testsuite name=\"script.yaml\" tests=\"4\" failures=\"2\" skipped=\"1\" time=\"1.500\">\
+             \n    <testcase name=\"echo &lt;a &amp; b&gt;\" classname=\"script.yaml\" time=\"1.500\">\
+             \n      <system-out>&lt;a &amp; b&gt;\n</system-out>\
+             \n    </testcase>\
+             \n    <testcase name=\"false\" classname=\"script.yaml\" time=\"0.000\">\
+             \n      <failure message=\"exit code 1\"/>\
+             \n      <system-err>[31mfailed[0m\n</system-err>\
+             \n    </testcase>\
+             \n    <testcase name=\"true\" classname=\"script.yaml\" time=\"0.000\">\
+             \n      <skipped/>\
+             \n    </testcase>\
+             \n    <testcase name=\"sleep 10\" classname=\"script.yaml\" time=\"0.000\">\
+             \n      <failure message=\"terminated by SIGKILL\"/>\
+             \n    </testcase>\
+             \n  </testsuite>\
+             \n</testsuites>\n"
+        );
+    }
+}
@@ -0,0 +1,172 @@
+//! Retry policy for run items which can fail occasionally
+//!
+//! # Example
+//!```
+//!use std::time::Duration;
+//!
+//!use automatic::retry::{Backoff, Retry};
+//!use automatic::runitem::RunItem;
+//!use automatic::run::Run;
+//!
+//!let status = RunItem::new("false".to_string(), vec![])
+//!    .with_retry(
+//!        Retry::new(3)
+//!            .with_delay(Duration::from_millis(10))
+//!            .with_backoff(Backoff::Exponential),
+//!    )
+//!    .run();
+//!
+//!assert_eq!(status.attempts().len(), 3);
+//!assert!(!status.status().unwrap().success());
+//!```
+
+use std::time::Duration;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::error::Error;
+use crate::runner::Output;
+
+/// Limit of delay which grows with exponential backoff
+pub const MAX_BACKOFF_DELAY: Duration = Duration::from_secs(3600);
+
+/// Growth of delay between attempts
+#[derive(Debug, PartialEq, Serialize, Deserialize, JsonSchema, Clone, Copy, Default)]
+pub enum Backoff {
+    /// Same delay before every next attempt
+    #[default]
+    Fixed,
+    /// Delay doubles before every next attempt
+    Exponential,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, JsonSchema, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Retry {
+    /// Maximum count of attempts, including the first one
+    attempts: u32,
+    /// Delay before the second attempt in seconds
+    #[serde(default, skip_serializing_if = "is_zero")]
+    delay: f64,
+    #[serde(default, skip_serializing_if = "is_fixed")]
+    backoff: Backoff,
+    /// Exit codes which are retried, any failure is retried if empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    codes: Vec<i32>,
+}
+
+fn is_zero(delay: &f64) -> bool {
+    *delay == 0.0
+}
+
+fn is_fixed(backoff: &Backoff) -> bool {
+    *backoff == Backoff::Fixed
+}
+
+impl Retry {
+    pub fn new(attempts: u32) -> Self {
+        Self {
+            attempts,
+            delay: 0.0,
+            backoff: Backoff::Fixed,
+            codes: vec![],
+        }
+    }
+
+    pub fn with_delay(mut self, delay: Duration) -> Self {
+        self.delay = delay.as_secs_f64();
+        self
+    }
+
+    pub fn with_backoff(mut self, backoff: Backoff) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    pub fn with_codes(mut self, codes: Vec<i32>) -> Self {
+        self.codes = codes;
+        self
+    }
+
+    pub fn attempts(&self) -> u32 {
+        self.attempts.max(1)
+    }
+
+    /// Delay before attempt with number `attempt`, first attempt is `1`,
+    /// exponential backoff doesn't grow delay over [`MAX_BACKOFF_DELAY`]
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let delay = Duration::try_from_secs_f64(self.delay).unwrap_or_default();
+        match (attempt, self.backoff) {
+            (0..=1, _) => Duration::from_secs(0),
+            (_, Backoff::Fixed) => delay,
+            (_, Backoff::Exponential) => delay
+                .checked_mul(2u32.saturating_pow(attempt - 2))
+                .unwrap_or(MAX_BACKOFF_DELAY)
+                .min(MAX_BACKOFF_DELAY),
+        }
+    }
+
+    /// Check is result of attempt retryable: failed exit with listed code
+    /// (or any failed exit and timeout if codes aren't listed)
+    pub fn retryable(&self, result: &Result<Output, Error>) -> bool {
+        match result {
+            Ok(output) if output.status.success() => false,
+            Ok(output) => match output.status.code() {
+                Some(code) => self.codes.is_empty() || self.codes.contains(&code),
+                None => self.codes.is_empty(),
+            },
+            Err(Error::Timeout { .. }) => self.codes.is_empty(),
+            Err(_) => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn delay() {
+        let retry = Retry::new(4).with_delay(Duration::from_secs(1));
+        assert_eq!(retry.delay(1), Duration::from_secs(0));
+        assert_eq!(retry.delay(2), Duration::from_secs(1));
+        assert_eq!(retry.delay(4), Duration::from_secs(1));
+
+        let retry = retry.with_backoff(Backoff::Exponential);
+        assert_eq!(retry.delay(2), Duration::from_secs(1));
+        assert_eq!(retry.delay(3), Duration::from_secs(2));
+        assert_eq!(retry.delay(4), Duration::from_secs(4));
+        assert_eq!(retry.delay(13), Duration::from_secs(2048));
+        assert_eq!(retry.delay(14), MAX_BACKOFF_DELAY);
+        assert_eq!(retry.delay(u32::MAX), MAX_BACKOFF_DELAY);
+
+        let retry = Retry::new(100)
+            .with_delay(Duration::from_secs(u64::MAX / 2))
+            .with_backoff(Backoff::Exponential);
+        assert_eq!(retry.delay(50), MAX_BACKOFF_DELAY);
+    }
+
+    #[test]
+    fn serde_retry() {
+        let retry = Retry::new(3)
+            .with_delay(Duration::from_millis(500))
+            .with_backoff(Backoff::Exponential)
+            .with_codes(vec![1, 75]);
+
+        let encoded = serde_yaml::to_string(&retry).unwrap();
+
+        assert_eq!(
+            encoded,
+            "---\nattempts: 3\ndelay: 0.5\nbackoff: Exponential\ncodes:\n  - 1\n  - 75\n"
+        );
+
+        let decoded: Retry = serde_yaml::from_str(&encoded).unwrap();
+
+        assert_eq!(decoded, retry);
+
+        let decoded: Retry = serde_yaml::from_str("attempts: 2").unwrap();
+
+        assert_eq!(decoded, Retry::new(2));
+    }
+}
@@ -1,7 +1,18 @@
 //! Run trait for unificate run any items
+//!
+//! # Example
+//!```
+//!use automatic::run::signal_name;
+//!
+//!assert_eq!(signal_name(9), "SIGKILL");
+//!assert_eq!(signal_name(1000), "signal 1000");
+//!```
 
 use std::fmt;
 
+use crate::error::Error;
+use crate::report::Report;
+
 /// Exit status collect trait
 ///
 /// Status collect item run state but have default state
@@ -18,6 +29,66 @@ pub trait ExitStatus {
     fn code(&self) -> Option<i32> {
         None
     }
+
+    /// Number of signal which terminated runned item, `None` if it exited
+    /// by itself or for not implemented state
+    fn signal(&self) -> Option<i32> {
+        None
+    }
+
+    /// Core dump was written when signal terminated runned item
+    fn core_dumped(&self) -> bool {
+        false
+    }
+
+    /// Name of signal which terminated runned item like `SIGKILL`
+    fn signal_name(&self) -> Option<String> {
+        self.signal().map(signal_name)
+    }
+}
+
+/// Name of Unix signal like `SIGKILL`, `signal N` for unknown signal
+pub fn signal_name(signal: i32) -> String {
+    #[cfg(unix)]
+    let name = match signal {
+        libc::SIGHUP => Some("SIGHUP"),
+        libc::SIGINT => Some("SIGINT"),
+        libc::SIGQUIT => Some("SIGQUIT"),
+        libc::SIGILL => Some("SIGILL"),
+        libc::SIGTRAP => Some("SIGTRAP"),
+        libc::SIGABRT => Some("SIGABRT"),
+        libc::SIGBUS => Some("SIGBUS"),
+        libc::SIGFPE => Some("SIGFPE"),
+        libc::SIGKILL => Some("SIGKILL"),
+        libc::SIGUSR1 => Some("SIGUSR1"),
+        libc::SIGSEGV => Some("SIGSEGV"),
+        libc::SIGUSR2 => Some("SIGUSR2"),
+        libc::SIGPIPE => Some("SIGPIPE"),
+        libc::SIGALRM => Some("SIGALRM"),
+        libc::SIGTERM => Some("SIGTERM"),
+        libc::SIGCHLD => Some("SIGCHLD"),
+        libc::SIGCONT => Some("SIGCONT"),
+        libc::SIGSTOP => Some("SIGSTOP"),
+        libc::SIGTSTP => Some("SIGTSTP"),
+        libc::SIGTTIN => Some("SIGTTIN"),
+        libc::SIGTTOU => Some("SIGTTOU"),
+        libc::SIGURG => Some("SIGURG"),
+        libc::SIGXCPU => Some("SIGXCPU"),
+        libc::SIGXFSZ => Some("SIGXFSZ"),
+        libc::SIGVTALRM => Some("SIGVTALRM"),
+        libc::SIGPROF => Some("SIGPROF"),
+        libc::SIGWINCH => Some("SIGWINCH"),
+        libc::SIGIO => Some("SIGIO"),
+        libc::SIGSYS => Some("SIGSYS"),
+        _ => None,
+    };
+    #[cfg(not(unix))]
+    let name: Option<&str> = None;
+
+    match name {
+        Some(name) => name.to_string(),
+        None => format!("signal {}", signal),
+    }
 }
 
 impl fmt::Debug for dyn ExitStatus {
@@ -42,7 +113,69 @@ impl PartialEq for dyn ExitStatus {
 /// Status collect current item state and other run item attributes
 pub trait RunStatus {
     /// Current Exit status of runned program or error of run command
-    fn status(&self) -> Result<Box<dyn ExitStatus>, String>;
+    fn status(&self) -> Result<Box<dyn ExitStatus>, Error>;
+
+    /// Statuses of every run attempt in order, the last one is the same as
+    /// `status()`
+    fn attempts(&self) -> Vec<Result<Box<dyn ExitStatus>, Error>> {
+        vec![self.status()]
+    }
+
+    /// Report of every runned item with its captured output
+    fn report(&self) -> Report {
+        Report::new()
+    }
+}
+
+fn exit_error(
+    item: &dyn fmt::Display,
+    status: Result<Box<dyn ExitStatus>, Error>,
+) -> Result<(), Error> {
+    let result = status?;
+    if result.success() {
+        return Ok(());
+    }
+
+    match (result.code(), result.signal()) {
+        (None, Some(signal)) => Err(Error::Signaled {
+            item: item.to_string(),
+            signal,
+            core_dumped: result.core_dumped(),
+        }),
+        (code, _) => Err(Error::NonZeroExit {
+            item: item.to_string(),
+            code: code.unwrap_or(-1),
+        }),
+    }
+}
+
+/// Check that run finished with success exit, error describes failure of
+/// every attempt for retried item
+pub(crate) fn check_exit(item: &dyn fmt::Display, status: &dyn RunStatus) -> Result<(), Error> {
+    attempts_error(item, status, exit_error(item, status.status()))
+}
+
+/// Check that item was run regardless of its exit status
+pub(crate) fn check_run(item: &dyn fmt::Display, status: &dyn RunStatus) -> Result<(), Error> {
+    attempts_error(item, status, status.status().map(|_| ()))
+}
+
+fn attempts_error(
+    item: &dyn fmt::Display,
+    status: &dyn RunStatus,
+    last: Result<(), Error>,
+) -> Result<(), Error> {
+    let attempts = status.attempts();
+    if last.is_ok() || attempts.len() < 2 {
+        return last;
+    }
+
+    Err(Error::Retried {
+        attempts: attempts
+            .into_iter()
+            .filter_map(|attempt| exit_error(item, attempt).err())
+            .collect(),
+    })
 }
 
 /// Run item trait
@@ -12,67 +12,923 @@
 //!assert!(result.success());
 //!```
 
-use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 
+use schemars::gen::SchemaGenerator;
+use schemars::schema::{InstanceType, Schema, SchemaObject};
+use schemars::JsonSchema;
+use serde::{de, Deserialize, Deserializer, Serialize};
+
+use crate::condition::Condition;
+use crate::document;
+use crate::error::Error;
+use crate::item::{FieldsVisitor, FromFields, ItemFields};
+use crate::report::{ItemReport, Report};
+use crate::retry::Retry;
 use crate::run::{ExitStatus, Run, RunMap, RunStatus};
 use crate::runner::{self, io, Output, Runned, Runner};
+use crate::vars::Vars;
+
+/// List level defaults for run items
+///
+/// Every run item of list inherits these parameters, parameters of item
+/// override defaults: `env` is merged, `env_remove` is joined and relative
+/// `cwd` of item is resolved against `cwd` of defaults.
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize, JsonSchema, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Defaults {
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    env: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    env_clear: Option<bool>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    env_remove: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    cwd: Option<PathBuf>,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "deserialize_seconds"
+    )]
+    timeout: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    process_group: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    shell: Option<Vec<String>>,
+}
+
+impl Defaults {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_env<K: Into<String>, V: Into<String>>(mut self, key: K, val: V) -> Self {
+        self.env.insert(key.into(), val.into());
+        self
+    }
+
+    pub fn with_env_clear(mut self, clear: bool) -> Self {
+        self.env_clear = Some(clear);
+        self
+    }
+
+    pub fn with_env_remove<K: Into<String>>(mut self, key: K) -> Self {
+        self.env_remove.push(key.into());
+        self
+    }
+
+    pub fn with_cwd<P: Into<PathBuf>>(mut self, cwd: P) -> Self {
+        self.cwd = Some(cwd.into());
+        self
+    }
+
+    /// Set default run time limit of items, see [`RunItem::with_timeout`]
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout.as_secs_f64());
+        self
+    }
+
+    /// Start items in their own process groups by default, see
+    /// [`RunItem::with_process_group`]
+    pub fn with_process_group(mut self, process_group: bool) -> Self {
+        self.process_group = Some(process_group);
+        self
+    }
+
+    /// Set shell of `shell` items, program with its arguments which gets
+    /// command line as the last argument, `/bin/sh -c` by default
+    pub fn with_shell<S: Into<String>>(mut self, shell: Vec<S>) -> Self {
+        self.shell = Some(shell.into_iter().map(Into::into).collect());
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    pub fn env(&self) -> &BTreeMap<String, String> {
+        &self.env
+    }
+
+    pub fn env_clear(&self) -> bool {
+        self.env_clear.unwrap_or(false)
+    }
+
+    pub fn env_remove(&self) -> &[String] {
+        &self.env_remove
+    }
+
+    pub fn cwd(&self) -> Option<&Path> {
+        self.cwd.as_deref()
+    }
+
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout.and_then(seconds)
+    }
+
+    pub fn process_group(&self) -> bool {
+        self.process_group.unwrap_or(false)
+    }
+
+    /// Apply defaults of outer list, own parameters take precedence
+    pub fn inherit(&self, parent: &Defaults) -> Self {
+        let mut env = parent.env.clone();
+        env.retain(|key, _| !self.env_remove.contains(key));
+        env.extend(self.env.clone());
+
+        let mut env_remove = parent.env_remove.clone();
+        env_remove.extend(
+            self.env_remove
+                .iter()
+                .filter(|key| !parent.env_remove.contains(key))
+                .cloned(),
+        );
+
+        let cwd = match (&parent.cwd, &self.cwd) {
+            (Some(base), Some(cwd)) => Some(base.join(cwd)),
+            (base, cwd) => cwd.clone().or_else(|| base.clone()),
+        };
+
+        Self {
+            env,
+            env_clear: self.env_clear.or(parent.env_clear),
+            env_remove,
+            cwd,
+            timeout: self.timeout.or(parent.timeout),
+            process_group: self.process_group.or(parent.process_group),
+            shell: self.shell.clone().or_else(|| parent.shell.clone()),
+        }
+    }
+
+    /// Substitute variables into env values and cwd
+    pub(crate) fn interpolate(&mut self, vars: &Vars) -> Result<(), Error> {
+        vars.interpolate_env(&mut self.env)?;
+        if let Some(cwd) = self.cwd.as_mut() {
+            vars.interpolate_path(cwd)?;
+        }
+        Ok(())
+    }
+}
 
-#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+/// Shell of `shell` items if it isn't set in defaults
+const DEFAULT_SHELL: [&str; 2] = ["/bin/sh", "-c"];
+
+/// Command of run item: program with arguments or command line run through
+/// shell
+#[derive(Debug, PartialEq, Serialize, Clone)]
+#[serde(untagged)]
+enum Command {
+    Argv { name: String, args: Vec<String> },
+    Shell { shell: String },
+}
+
+/// Schema of command flattened into run item: either `name` with `args` or
+/// `shell`, the same fields which [`ItemFields`] reads
+fn command_schema(gen: &mut SchemaGenerator) -> Schema {
+    let required = |fields: &[&str]| {
+        let mut schema = SchemaObject::default();
+        schema.object().required = fields.iter().map(|f| f.to_string()).collect();
+        schema
+    };
+    let command = |fields: &[&str], others: &[&str]| {
+        let mut other = SchemaObject::default();
+        other.subschemas().any_of = Some(
+            others
+                .iter()
+                .map(|field| Schema::Object(required(&[field])))
+                .collect(),
+        );
+        let mut schema = required(fields);
+        schema.subschemas().not = Some(Box::new(Schema::Object(other)));
+        Schema::Object(schema)
+    };
+
+    let mut schema = SchemaObject {
+        instance_type: Some(InstanceType::Object.into()),
+        ..Default::default()
+    };
+    let object = schema.object();
+    object
+        .properties
+        .insert("name".to_string(), gen.subschema_for::<String>());
+    object
+        .properties
+        .insert("args".to_string(), gen.subschema_for::<Vec<String>>());
+    object
+        .properties
+        .insert("shell".to_string(), gen.subschema_for::<String>());
+    object.additional_properties = Some(Box::new(false.into()));
+    schema.subschemas().one_of = Some(vec![
+        command(&["name", "args"], &["shell"]),
+        command(&["shell"], &["name", "args"]),
+    ]);
+    Schema::Object(schema)
+}
+
+/// Command which list runs with its environment and run options
+#[derive(Debug, PartialEq, Serialize, JsonSchema, Clone)]
+#[schemars(deny_unknown_fields)]
 pub struct RunItem {
-    name: String,
-    args: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    id: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    needs: Vec<String>,
+    #[serde(flatten)]
+    #[schemars(schema_with = "command_schema")]
+    command: Command,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    env: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    env_clear: Option<bool>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    env_remove: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    cwd: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    timeout: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    process_group: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    retry: Option<Retry>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    success_codes: Vec<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    ignore_failure: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    when: Option<Box<Condition>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    unless: Option<Box<Condition>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    when_cmd: Option<String>,
+    /// Shell of `shell` item inherited from list defaults
+    #[serde(skip)]
+    shell: Option<Vec<String>>,
+}
+
+impl<'de> Deserialize<'de> for RunItem {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        document::recover(
+            deserializer.deserialize_map(FieldsVisitor(PhantomData)),
+            || RunItem::shell(String::new()),
+        )
+    }
+}
+
+/// Command is checked after all fields are read, so errors of fields keep
+/// their position
+impl FromFields for RunItem {
+    fn from_fields<E: de::Error>(mut fields: ItemFields) -> Result<Self, E> {
+        if let Some(list) = fields.take_lists().first() {
+            return Err(E::custom(format!("expected run item, found {}", list)));
+        }
+        if fields.include.is_some() || fields.target.is_some() {
+            return Err(E::custom("expected run item, found include"));
+        }
+        let command = match (fields.name, fields.args, fields.shell) {
+            (Some(name), Some(args), None) => Command::Argv { name, args },
+            (None, None, Some(shell)) => Command::Shell { shell },
+            (Some(_), None, None) => return Err(E::missing_field("args")),
+            (None, Some(_), None) => return Err(E::missing_field("name")),
+            (None, None, None) => return Err(E::custom("item has neither `name` nor `shell`")),
+            (_, _, Some(_)) => {
+                return Err(E::custom("item has `shell` together with `name` or `args`"))
+            }
+        };
+        Ok(RunItem {
+            id: fields.id,
+            needs: fields.needs,
+            command,
+            env: fields.env,
+            env_clear: fields.env_clear,
+            env_remove: fields.env_remove,
+            cwd: fields.cwd,
+            timeout: fields.timeout,
+            process_group: fields.process_group,
+            retry: fields.retry,
+            success_codes: fields.success_codes,
+            ignore_failure: fields.ignore_failure,
+            when: fields.when,
+            unless: fields.unless,
+            when_cmd: fields.when_cmd,
+            shell: None,
+        })
+    }
 }
 
 impl RunItem {
     pub fn new(name: String, args: Vec<String>) -> Self {
-        Self { name, args }
+        Self::from_command(Command::Argv { name, args })
+    }
+
+    /// Create item which runs command line through shell, see
+    /// [`Defaults::with_shell`]
+    pub fn shell(command: String) -> Self {
+        Self::from_command(Command::Shell { shell: command })
+    }
+
+    fn from_command(command: Command) -> Self {
+        Self {
+            id: None,
+            needs: vec![],
+            command,
+            env: BTreeMap::new(),
+            env_clear: None,
+            env_remove: vec![],
+            cwd: None,
+            timeout: None,
+            process_group: None,
+            retry: None,
+            success_codes: vec![],
+            ignore_failure: None,
+            when: None,
+            unless: None,
+            when_cmd: None,
+            shell: None,
+        }
+    }
+
+    /// Program of item or command line of `shell` item
+    pub fn name(&self) -> &str {
+        match &self.command {
+            Command::Argv { name, .. } => name,
+            Command::Shell { shell } => shell,
+        }
+    }
+
+    /// Program which item spawns, shell of `shell` item
+    pub fn program(&self) -> &str {
+        match (&self.command, &self.shell) {
+            (Command::Argv { name, .. }, _) => name,
+            (Command::Shell { .. }, Some(program)) if !program.is_empty() => &program[0],
+            (Command::Shell { .. }, _) => DEFAULT_SHELL[0],
+        }
+    }
+
+    /// Arguments of program, empty for `shell` item
+    pub fn args(&self) -> &[String] {
+        match &self.command {
+            Command::Argv { args, .. } => args,
+            Command::Shell { .. } => &[],
+        }
+    }
+
+    /// Identifier of item which other items of graph list refer to
+    pub fn id(&self) -> Option<&str> {
+        self.id.as_deref()
+    }
+
+    /// Identifiers of items which should succeed before run of this item
+    pub fn needs(&self) -> &[String] {
+        &self.needs
+    }
+
+    pub fn is_shell(&self) -> bool {
+        matches!(self.command, Command::Shell { .. })
+    }
+
+    pub fn env(&self) -> &BTreeMap<String, String> {
+        &self.env
+    }
+
+    pub fn env_clear(&self) -> bool {
+        self.env_clear.unwrap_or(false)
+    }
+
+    pub fn env_remove(&self) -> &[String] {
+        &self.env_remove
+    }
+
+    pub fn cwd(&self) -> Option<&Path> {
+        self.cwd.as_deref()
+    }
+
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout.and_then(seconds)
+    }
+
+    pub fn process_group(&self) -> bool {
+        self.process_group.unwrap_or(false)
+    }
+
+    pub fn with_env<K: Into<String>, V: Into<String>>(mut self, key: K, val: V) -> Self {
+        self.env.insert(key.into(), val.into());
+        self
+    }
+
+    pub fn with_env_clear(mut self, clear: bool) -> Self {
+        self.env_clear = Some(clear);
+        self
+    }
+
+    pub fn with_env_remove<K: Into<String>>(mut self, key: K) -> Self {
+        self.env_remove.push(key.into());
+        self
+    }
+
+    pub fn with_cwd<P: Into<PathBuf>>(mut self, cwd: P) -> Self {
+        self.cwd = Some(cwd.into());
+        self
+    }
+
+    /// Set run time limit, after it the process gets SIGTERM and then
+    /// SIGKILL if it doesn't exit during grace period
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout.as_secs_f64());
+        self
+    }
+
+    /// Start item in new process group, termination on timeout or
+    /// cancellation kills processes started by item too. Item in own group
+    /// doesn't get signals of terminal and can't read from it
+    pub fn with_process_group(mut self, process_group: bool) -> Self {
+        self.process_group = Some(process_group);
+        self
+    }
+
+    /// Set policy of run repeating on failure
+    pub fn with_retry(mut self, retry: Retry) -> Self {
+        self.retry = Some(retry);
+        self
+    }
+
+    pub fn with_id<S: Into<String>>(mut self, id: S) -> Self {
+        self.id = Some(id.into());
+        self
+    }
+
+    pub fn with_needs<S: Into<String>>(mut self, needs: Vec<S>) -> Self {
+        self.needs = needs.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Set exit codes treated as success instead of `0`
+    pub fn with_success_codes(mut self, codes: Vec<i32>) -> Self {
+        self.success_codes = codes;
+        self
+    }
+
+    /// Treat failure of item as success, list continues to run and the
+    /// failure is marked as ignored in run report
+    pub fn with_ignore_failure(mut self, ignore: bool) -> Self {
+        self.ignore_failure = Some(ignore);
+        self
+    }
+
+    /// Run item only if condition holds
+    pub fn with_when(mut self, condition: Condition) -> Self {
+        self.when = Some(Box::new(condition));
+        self
+    }
+
+    /// Skip item if condition holds
+    pub fn with_unless(mut self, condition: Condition) -> Self {
+        self.unless = Some(Box::new(condition));
+        self
+    }
+
+    /// Run item only if guard command line exits successfully, it's run
+    /// through shell with env and cwd of item
+    pub fn with_when_cmd(mut self, command: String) -> Self {
+        self.when_cmd = Some(command);
+        self
+    }
+
+    /// Check conditions of item before its spawn, `false` if item should be
+    /// skipped
+    pub(crate) fn should_run(&self) -> Result<bool, Error> {
+        if self.when.as_ref().is_some_and(|when| !when.holds(self))
+            || self
+                .unless
+                .as_ref()
+                .is_some_and(|unless| unless.holds(self))
+        {
+            return Ok(false);
+        }
+        match &self.when_cmd {
+            Some(command) => {
+                let mut runner = self.shell_runner(command);
+                self.configure(&mut runner);
+                let output = runner.run().map_err(|e| Error::Spawn {
+                    item: command.clone(),
+                    source: e,
+                })?;
+                Ok(output.status.success())
+            }
+            None => Ok(true),
+        }
+    }
+
+    /// Exit of item is successful: its code is one of success codes
+    pub fn exit_success(&self, status: &runner::ExitStatus) -> bool {
+        if self.success_codes.is_empty() {
+            return status.success();
+        }
+        match status.code() {
+            Some(code) => self.success_codes.contains(&code),
+            None => false,
+        }
+    }
+
+    /// Substitute variables into command, env values, cwd and conditions
+    pub(crate) fn interpolate(&mut self, vars: &Vars) -> Result<(), Error> {
+        match &mut self.command {
+            Command::Argv { name, args } => {
+                *name = vars.interpolate(name)?;
+                for arg in args.iter_mut() {
+                    *arg = vars.interpolate(arg)?;
+                }
+            }
+            Command::Shell { shell } => *shell = vars.interpolate(shell)?,
+        }
+        vars.interpolate_env(&mut self.env)?;
+        if let Some(cwd) = self.cwd.as_mut() {
+            vars.interpolate_path(cwd)?;
+        }
+        for condition in self.when.iter_mut().chain(self.unless.iter_mut()) {
+            condition.interpolate(vars)?;
+        }
+        if let Some(command) = self.when_cmd.as_mut() {
+            *command = vars.interpolate(command)?;
+        }
+        Ok(())
+    }
+
+    /// Apply list defaults to run item, item parameters take precedence
+    pub fn inherit(&self, defaults: &Defaults) -> Self {
+        let own = Defaults {
+            env: self.env.clone(),
+            env_clear: self.env_clear,
+            env_remove: self.env_remove.clone(),
+            cwd: self.cwd.clone(),
+            timeout: self.timeout,
+            process_group: self.process_group,
+            shell: self.shell.clone(),
+        }
+        .inherit(defaults);
+
+        Self {
+            id: self.id.clone(),
+            needs: self.needs.clone(),
+            command: self.command.clone(),
+            env: own.env,
+            env_clear: own.env_clear,
+            env_remove: own.env_remove,
+            cwd: own.cwd,
+            timeout: own.timeout,
+            process_group: own.process_group,
+            retry: self.retry.clone(),
+            success_codes: self.success_codes.clone(),
+            ignore_failure: self.ignore_failure,
+            when: self.when.clone(),
+            unless: self.unless.clone(),
+            when_cmd: self.when_cmd.clone(),
+            shell: own.shell,
+        }
     }
 
     pub fn run_async(&self) -> Runned {
-        Runner::new(self.name.clone(), self.args.clone()).run_async()
+        self.runner().run_async()
+    }
+
+    pub(crate) fn runner(&self) -> Runner {
+        let mut runner = match &self.command {
+            Command::Argv { name, args } => Runner::new(name.clone(), args.clone()),
+            Command::Shell { shell } => self.shell_runner(shell),
+        };
+        self.configure(&mut runner);
+        if let Some(timeout) = self.timeout() {
+            runner.set_timeout(timeout);
+        }
+        if self.process_group == Some(true) {
+            runner.set_process_group();
+        }
+        runner
+    }
+
+    /// Runner of command line through shell of item
+    fn shell_runner(&self, command: &str) -> Runner {
+        let mut program: Vec<String> = match &self.shell {
+            Some(program) if !program.is_empty() => program.clone(),
+            _ => DEFAULT_SHELL.iter().map(|arg| arg.to_string()).collect(),
+        };
+        program.push(command.to_string());
+        let name = program.remove(0);
+        Runner::new(name, program)
+    }
+
+    /// Apply env and cwd of item to runner
+    fn configure(&self, runner: &mut Runner) {
+        if self.env_clear == Some(true) {
+            runner.set_env_clear();
+        }
+        for key in self.env_remove.iter() {
+            runner.set_env_remove(key);
+        }
+        for (key, val) in self.env.iter() {
+            runner.set_env(key, val);
+        }
+        if let Some(cwd) = &self.cwd {
+            runner.set_cwd(cwd);
+        }
+    }
+
+    fn run_once(&self, runner: &mut Runner) -> Attempt {
+        self.attempt(runner.run())
+    }
+
+    /// Attempt of item from result of its runner
+    pub(crate) fn attempt(&self, result: io::Result<Output>) -> Attempt {
+        match result {
+            Ok(output) => Attempt {
+                result: Ok(output),
+                stopped: None,
+            },
+            Err(e) => Attempt {
+                stopped: runner::stopped_output(&e).cloned(),
+                result: Err(self.run_error(e)),
+            },
+        }
+    }
+
+    /// Describe error of runner of this item
+    pub(crate) fn run_error(&self, e: io::Error) -> Error {
+        match e.kind() {
+            io::ErrorKind::TimedOut => Error::Timeout {
+                item: self.to_string(),
+                timeout: self.timeout().unwrap_or_default(),
+            },
+            io::ErrorKind::Interrupted => Error::Cancelled {
+                item: self.to_string(),
+            },
+            _ => Error::Spawn {
+                item: self.to_string(),
+                source: e,
+            },
+        }
+    }
+
+    /// Status of item which was run outside of `Run` trait
+    pub(crate) fn status(&self, attempts: Vec<Attempt>, duration: Duration) -> Box<dyn RunStatus> {
+        Box::new(RunItemStatus {
+            item: self.clone(),
+            attempts,
+            duration,
+        })
+    }
+
+    /// Status of item skipped because of its conditions
+    pub(crate) fn skipped(&self) -> Box<dyn RunStatus> {
+        Box::new(SkippedStatus { item: self.clone() })
+    }
+
+    fn run_attempts(&self, runner: &mut Runner) -> Box<dyn RunStatus> {
+        let start = Instant::now();
+        match self.should_run() {
+            Ok(true) => {}
+            Ok(false) => return self.skipped(),
+            Err(e) => return self.status(vec![e.into()], start.elapsed()),
+        }
+        let mut attempts = vec![self.run_once(runner)];
+        if let Some(retry) = &self.retry {
+            for attempt in 2..=retry.attempts() {
+                let last = &attempts.last().unwrap().result;
+                if matches!(last, Ok(output) if self.exit_success(&output.status))
+                    || !retry.retryable(last)
+                {
+                    break;
+                }
+                if let Err(e) = runner.sleep(retry.delay(attempt)) {
+                    attempts.push(self.run_error(e).into());
+                    break;
+                }
+                attempts.push(self.run_once(runner));
+            }
+        }
+        self.status(attempts, start.elapsed())
+    }
+}
+
+impl fmt::Display for RunItem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())?;
+        for arg in self.args() {
+            write!(f, " {}", arg)?;
+        }
+        Ok(())
+    }
+}
+
+/// Duration of `secs` seconds, `None` if it isn't representable
+fn seconds(secs: f64) -> Option<Duration> {
+    Duration::try_from_secs_f64(secs).ok()
+}
+
+pub(crate) fn deserialize_seconds<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<f64>, D::Error> {
+    match Option::<f64>::deserialize(deserializer)? {
+        Some(secs) if seconds(secs).is_none() => Err(de::Error::custom(format!(
+            "invalid timeout: {}, expected non-negative number of seconds",
+            secs
+        ))),
+        secs => Ok(secs),
+    }
+}
+
+/// Run of item, output of process which was stopped on timeout or
+/// cancellation is kept with error of run
+pub(crate) struct Attempt {
+    result: Result<Output, Error>,
+    stopped: Option<Output>,
+}
+
+impl From<Error> for Attempt {
+    fn from(e: Error) -> Self {
+        Self {
+            result: Err(e),
+            stopped: None,
+        }
     }
 }
 
 struct RunItemStatus {
-    status: io::Result<Output>,
+    item: RunItem,
+    attempts: Vec<Attempt>,
+    duration: Duration,
 }
 
 struct StatusHelper {
     status: runner::ExitStatus,
+    success: bool,
 }
 
 impl ExitStatus for StatusHelper {
     fn success(&self) -> bool {
-        self.status.success()
+        self.success
     }
 
     fn code(&self) -> Option<i32> {
         self.status.code()
     }
+
+    fn signal(&self) -> Option<i32> {
+        exit_signal(&self.status)
+    }
+
+    fn core_dumped(&self) -> bool {
+        exit_core_dumped(&self.status)
+    }
+}
+
+/// Signal which terminated process
+fn exit_signal(status: &runner::ExitStatus) -> Option<i32> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+
+        status.signal()
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = status;
+        None
+    }
+}
+
+/// Process terminated by signal dumped core
+fn exit_core_dumped(status: &runner::ExitStatus) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+
+        status.core_dumped()
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = status;
+        false
+    }
+}
+
+/// Successful status of item which failure is ignored or which is skipped,
+/// it has exit code of the item if it was run
+struct IgnoredStatus {
+    code: Option<i32>,
+}
+
+impl ExitStatus for IgnoredStatus {
+    fn code(&self) -> Option<i32> {
+        self.code
+    }
+}
+
+impl RunItemStatus {
+    fn attempt_status(
+        &self,
+        attempt: &Result<Output, Error>,
+    ) -> Result<Box<dyn ExitStatus>, Error> {
+        match attempt {
+            Ok(ok) => Ok(Box::new(StatusHelper {
+                status: ok.status,
+                success: self.item.exit_success(&ok.status),
+            })),
+            Err(e) => Err(e.clone()),
+        }
+    }
+
+    /// Item failed but its failure is ignored
+    fn ignored(&self) -> bool {
+        self.item.ignore_failure == Some(true)
+            && !matches!(&self.attempts.last().unwrap().result, Ok(output) if self.item.exit_success(&output.status))
+    }
 }
 
 impl RunStatus for RunItemStatus {
-    fn status(&self) -> Result<Box<dyn ExitStatus>, String> {
-        match &self.status {
-            Ok(ok) => Ok(Box::new(StatusHelper { status: ok.status })),
-            Err(e) => Err(format!("{}", e)),
+    fn status(&self) -> Result<Box<dyn ExitStatus>, Error> {
+        let status = self.attempt_status(&self.attempts.last().unwrap().result);
+        if self.ignored() {
+            return Ok(Box::new(IgnoredStatus {
+                code: status.ok().and_then(|status| status.code()),
+            }));
+        }
+        status
+    }
+
+    fn attempts(&self) -> Vec<Result<Box<dyn ExitStatus>, Error>> {
+        self.attempts
+            .iter()
+            .map(|attempt| self.attempt_status(&attempt.result))
+            .collect()
+    }
+
+    fn report(&self) -> Report {
+        let mut report = ItemReport {
+            name: self.item.name().to_string(),
+            args: self.item.args().to_vec(),
+            duration: self.duration,
+            attempts: self.attempts.len(),
+            success_codes: self.item.success_codes.clone(),
+            ignored: self.ignored(),
+            ..ItemReport::default()
+        };
+        let last = self.attempts.last().unwrap();
+        match &last.result {
+            Ok(output) => {
+                report.code = output.status.code();
+                report.signal = exit_signal(&output.status);
+                report.core_dumped = exit_core_dumped(&output.status);
+                report.stdout = output.stdout.clone();
+                report.stderr = output.stderr.clone();
+            }
+            Err(e) => report.error = Some(e.clone()),
+        }
+        if let Some(output) = &last.stopped {
+            report.stdout = output.stdout.clone();
+            report.stderr = output.stderr.clone();
+        }
+        Report {
+            items: vec![report],
+        }
+    }
+}
+
+/// Status of skipped item, it's successful without exit code
+struct SkippedStatus {
+    item: RunItem,
+}
+
+impl RunStatus for SkippedStatus {
+    fn status(&self) -> Result<Box<dyn ExitStatus>, Error> {
+        Ok(Box::new(IgnoredStatus { code: None }))
+    }
+
+    fn report(&self) -> Report {
+        Report {
+            items: vec![ItemReport {
+                name: self.item.name().to_string(),
+                args: self.item.args().to_vec(),
+                skipped: true,
+                ..ItemReport::default()
+            }],
         }
     }
 }
 
 impl Run for RunItem {
     fn run(&self) -> Box<dyn RunStatus> {
-        Box::new(RunItemStatus {
-            status: Runner::new(self.name.clone(), self.args.clone()).run(),
-        })
+        self.run_attempts(&mut self.runner())
     }
 }
 
 impl RunMap<Runner> for RunItem {
     fn run_map<F: FnOnce(&mut Runner) -> &mut Runner>(&self, op: F) -> Box<dyn RunStatus> {
-        Box::new(RunItemStatus {
-            status: op(&mut Runner::new(self.name.clone(), self.args.clone())).run(),
-        })
+        let mut runner = self.runner();
+        op(&mut runner);
+        self.run_attempts(&mut runner)
     }
 }
 
@@ -80,6 +936,7 @@ impl RunMap<Runner> for RunItem {
 mod tests {
     use os_pipe::pipe;
     use std::io::prelude::*;
+    use std::thread;
 
     use super::*;
 
@@ -93,11 +950,7 @@ mod tests {
 
         let result = RunItem::new(
             "true".to_string(),
-            vec!["1", "2", "3"]
-                .iter()
-                .cloned()
-                .map(String::from)
-                .collect(),
+            ["1", "2", "3"].iter().cloned().map(String::from).collect(),
         )
         .run()
         .status()
@@ -123,6 +976,205 @@ mod tests {
         );
     }
 
+    #[test]
+    fn create_with_cwd_output_to_pipe() {
+        let (mut reader, writer) = pipe().unwrap();
+
+        let result = RunItem::new("ls".to_string(), vec![])
+            .with_cwd("tests")
+            .run_map(|r| r.set_stdout(writer))
+            .status()
+            .expect("failed to execute process");
+        assert!(result.success());
+
+        let mut output = String::new();
+        reader.read_to_string(&mut output).unwrap();
+        assert!(output.contains("test_script.yaml\n"));
+    }
+
+    #[test]
+    fn inherit_defaults() {
+        let defaults = Defaults::new()
+            .with_env("A", "list")
+            .with_env("B", "list")
+            .with_env_remove("C")
+            .with_cwd("/tmp");
+
+        let item = RunItem::new("true".to_string(), vec![])
+            .with_env("B", "item")
+            .with_env_clear(false)
+            .with_env_remove("D")
+            .with_cwd("dir")
+            .inherit(&defaults);
+
+        assert_eq!(
+            item,
+            RunItem::new("true".to_string(), vec![])
+                .with_env("A", "list")
+                .with_env("B", "item")
+                .with_env_clear(false)
+                .with_env_remove("C")
+                .with_env_remove("D")
+                .with_cwd("/tmp/dir")
+        );
+
+        let item = RunItem::new("true".to_string(), vec![]).inherit(&Defaults::new());
+        assert_eq!(item, RunItem::new("true".to_string(), vec![]));
+    }
+
+    #[test]
+    fn timeout() {
+        let result = RunItem::new("sleep".to_string(), vec!["10".to_string()])
+            .with_timeout(Duration::from_millis(100))
+            .run()
+            .status();
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "`sleep 10` timed out after 0.1s"
+        );
+
+        let report = RunItem::shell("echo started; echo failed >&2; exec sleep 10".to_string())
+            .with_timeout(Duration::from_millis(200))
+            .run()
+            .report();
+        assert!(matches!(report.items[0].error, Some(Error::Timeout { .. })));
+        assert_eq!(report.items[0].stdout, b"started\n");
+        assert_eq!(report.items[0].stderr, b"failed\n");
+
+        let item = RunItem::new("true".to_string(), vec![])
+            .inherit(&Defaults::new().with_timeout(Duration::from_secs(1)));
+        assert_eq!(
+            item,
+            RunItem::new("true".to_string(), vec![]).with_timeout(Duration::from_secs(1))
+        );
+
+        for timeout in &["-1", ".nan", "1e30"] {
+            let decoded: Result<RunItem, _> =
+                serde_yaml::from_str(&format!("name: \"true\"\nargs: []\ntimeout: {}", timeout));
+            assert!(decoded.is_err(), "timeout {}", timeout);
+        }
+
+        // Deadline out of range of clock means no deadline
+        let decoded: RunItem =
+            serde_yaml::from_str("name: \"true\"\nargs: []\ntimeout: 1.8e19").unwrap();
+        assert!(decoded.run().status().unwrap().success());
+    }
+
+    #[test]
+    fn process_group() {
+        let marker = std::env::temp_dir().join(format!("automatic-group-{}", std::process::id()));
+        let command = format!("(sleep 1; touch {}) & wait", marker.display());
+        let result = RunItem::shell(command)
+            .with_timeout(Duration::from_millis(100))
+            .with_process_group(true)
+            .run()
+            .status();
+        assert!(result.is_err());
+
+        // Background job of timed out item is killed with it
+        thread::sleep(Duration::from_millis(1500));
+        assert!(!marker.exists());
+
+        let item = RunItem::new("true".to_string(), vec![])
+            .inherit(&Defaults::new().with_process_group(true));
+        assert!(item.process_group());
+    }
+
+    #[test]
+    fn retry() {
+        let status = RunItem::new("false".to_string(), vec![])
+            .with_retry(Retry::new(3))
+            .run();
+        let attempts = status.attempts();
+        assert_eq!(attempts.len(), 3);
+        assert!(attempts
+            .iter()
+            .all(|a| a.as_ref().unwrap().code() == Some(1)));
+
+        let status = RunItem::new("false".to_string(), vec![])
+            .with_retry(Retry::new(3).with_codes(vec![75]))
+            .run();
+        assert_eq!(status.attempts().len(), 1);
+
+        let status = RunItem::new("bla bla".to_string(), vec![])
+            .with_retry(Retry::new(3))
+            .run();
+        assert_eq!(status.attempts().len(), 1);
+
+        // Fails on the first attempt and succeeds on the second one
+        let marker = std::env::temp_dir().join(format!("automatic-retry-{}", std::process::id()));
+        let _ = std::fs::remove_file(&marker);
+        let script = format!("test -f {0} || {{ touch {0}; exit 75; }}", marker.display());
+        let status = RunItem::new("sh".to_string(), vec!["-c".to_string(), script])
+            .with_retry(Retry::new(3).with_codes(vec![75]))
+            .run();
+        let _ = std::fs::remove_file(&marker);
+        let attempts = status.attempts();
+        assert_eq!(attempts.len(), 2);
+        assert_eq!(attempts[0].as_ref().unwrap().code(), Some(75));
+        assert!(status.status().unwrap().success());
+
+        // Cancelled run doesn't wait for delay of the next attempt
+        let cancel = runner::Cancel::new();
+        let flag = cancel.clone();
+        let handle = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(100));
+            flag.raise();
+        });
+        let start = Instant::now();
+        let status = RunItem::new("false".to_string(), vec![])
+            .with_retry(Retry::new(3).with_delay(Duration::from_secs(20)))
+            .run_map(|r| r.add_cancel(cancel));
+        handle.join().unwrap();
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert_eq!(status.attempts().len(), 2);
+        assert!(matches!(
+            status.status().unwrap_err(),
+            Error::Cancelled { .. }
+        ));
+    }
+
+    #[test]
+    fn report() {
+        let report = RunItem::new(
+            "sh".to_string(),
+            vec!["-c".to_string(), "sleep 0.1; echo ok".to_string()],
+        )
+        .run()
+        .report();
+        assert_eq!(report.items.len(), 1);
+
+        let item = &report.items[0];
+        assert_eq!(item.args, vec!["-c", "sleep 0.1; echo ok"]);
+        assert_eq!(item.code, Some(0));
+        assert_eq!(item.stdout, b"ok\n");
+        assert_eq!(item.attempts, 1);
+        assert!(item.duration >= Duration::from_millis(100));
+
+        let report = RunItem::new("bla bla".to_string(), vec![]).run().report();
+        assert!(matches!(report.items[0].error, Some(Error::Spawn { .. })));
+    }
+
+    #[test]
+    fn env_and_cwd() {
+        let (mut reader, writer) = pipe().unwrap();
+
+        let result = RunItem::new(
+            "sh".to_string(),
+            vec!["-c".to_string(), "echo $AUTOMATIC_TEST; pwd".to_string()],
+        )
+        .with_env("AUTOMATIC_TEST", "value")
+        .with_cwd("/")
+        .run_map(|r| r.set_stdout(writer))
+        .status()
+        .expect("failed to execute process");
+        assert!(result.success());
+
+        let mut output = String::new();
+        reader.read_to_string(&mut output).unwrap();
+        assert_eq!(output, "value\n/\n");
+    }
+
     #[test]
     fn create_async() {
         let result = RunItem::new("true".to_string(), vec![])
@@ -133,15 +1185,154 @@ mod tests {
 
         let result = RunItem::new(
             "true".to_string(),
-            vec!["1", "2", "3"]
-                .iter()
-                .cloned()
-                .map(String::from)
-                .collect(),
+            ["1", "2", "3"].iter().cloned().map(String::from).collect(),
         )
         .run_async()
         .wait()
         .expect("failed to wait");
         assert!(result.success());
     }
+
+    #[test]
+    fn shell() {
+        let item = RunItem::shell("ls *.rs && echo \"$0\" > /dev/null".to_string()).with_cwd("src");
+
+        let report = item.run().report();
+        assert!(report.items[0].success());
+        assert_eq!(report.items[0].name, "ls *.rs && echo \"$0\" > /dev/null");
+        assert!(report.items[0].args.is_empty());
+        assert!(String::from_utf8_lossy(&report.items[0].stdout).contains("runitem.rs"));
+
+        let report = RunItem::shell("echo \"$-\"".to_string())
+            .inherit(&Defaults::new().with_shell(vec!["sh", "-euc"]))
+            .run()
+            .report();
+        assert!(String::from_utf8_lossy(&report.items[0].stdout).contains('u'));
+
+        let encoded = serde_yaml::to_string(&item).unwrap();
+        assert_eq!(
+            encoded,
+            "---\nshell: \"ls *.rs && echo \\\"$0\\\" > /dev/null\"\ncwd: src\n"
+        );
+        let decoded: RunItem = serde_yaml::from_str(&encoded).unwrap();
+        assert_eq!(decoded, item);
+    }
+
+    #[test]
+    fn deserialize_command() {
+        let error = |s: &str| {
+            serde_yaml::from_str::<Vec<RunItem>>(s)
+                .unwrap_err()
+                .to_string()
+        };
+
+        assert_eq!(
+            error("---\n- shell: \"true\"\n- name: ls\n  timeout: x\n"),
+            ".[1].timeout: invalid type: string \"x\", expected f64 at line 4 column 12"
+        );
+        assert_eq!(
+            error("---\n- shell: \"true\"\n- name: ls\n  cwd: src\n"),
+            ".[1]: missing field `args` at line 3 column 7"
+        );
+        assert_eq!(
+            error("---\n- args: []\n"),
+            ".[0]: missing field `name` at line 2 column 7"
+        );
+        assert_eq!(
+            error("---\n- cwd: src\n"),
+            ".[0]: item has neither `name` nor `shell` at line 2 column 6"
+        );
+        assert!(error("---\n- shell: ls\n  name: ls\n  args: []\n")
+            .starts_with(".[0]: item has `shell` together with `name` or `args`"));
+        assert_eq!(
+            error("---\n- include: a.yaml\n"),
+            ".[0]: expected run item, found include at line 2 column 10"
+        );
+        assert_eq!(
+            error("---\n- Silent:\n    items: []\n"),
+            ".[0]: expected run item, found Silent list at line 2 column 9"
+        );
+    }
+
+    #[test]
+    fn shell_interpolation() {
+        let vars = Vars::new().with_var("NAME", "automatic");
+
+        // Shell expands escaped reference, script expands the other one
+        let mut item = RunItem::shell("echo \"$${AUTOMATIC_SHELL_VAR}\" ${NAME}".to_string())
+            .with_env("AUTOMATIC_SHELL_VAR", "shell");
+        item.interpolate(&vars).unwrap();
+        assert_eq!(
+            item.to_string(),
+            "echo \"${AUTOMATIC_SHELL_VAR}\" automatic"
+        );
+        let report = item.run().report();
+        assert_eq!(
+            String::from_utf8_lossy(&report.items[0].stdout),
+            "shell automatic\n"
+        );
+
+        let mut item = RunItem::shell("echo ${HOME}".to_string());
+        assert_eq!(
+            item.interpolate(&vars).unwrap_err().to_string(),
+            "undefined variable `HOME`"
+        );
+    }
+
+    #[test]
+    fn success_codes_and_ignore_failure() {
+        let exit = |code: i32| RunItem::shell(format!("echo {0}; exit {0}", code));
+
+        let status = exit(3).with_success_codes(vec![0, 3]).run();
+        assert!(status.status().unwrap().success());
+        assert!(status.report().items[0].success());
+
+        let status = exit(0).with_success_codes(vec![3]).run();
+        assert!(!status.status().unwrap().success());
+
+        let status = exit(4).with_ignore_failure(true).run();
+        let result = status.status().unwrap();
+        assert!(result.success());
+        assert_eq!(result.code(), Some(4));
+
+        let report = status.report();
+        assert!(!report.items[0].success());
+        assert!(report.items[0].ignored);
+        assert_eq!(report.items[0].stdout, b"4\n");
+
+        let status = RunItem::new("bla bla".to_string(), vec![])
+            .with_ignore_failure(true)
+            .run();
+        assert!(status.status().unwrap().success());
+        assert!(status.report().items[0].error.is_some());
+    }
+
+    #[test]
+    fn conditions() {
+        let item = RunItem::new("true".to_string(), vec![]).with_cwd("tests");
+
+        let report = item
+            .clone()
+            .with_when(Condition::new().with_exists("test_script.yaml"))
+            .with_unless(Condition::new().with_env("AUTOMATIC_NO_SUCH_VAR"))
+            .run()
+            .report();
+        assert!(report.items[0].success());
+        assert_eq!(report.skipped().count(), 0);
+
+        let report = item
+            .clone()
+            .with_when_cmd("test -f no_such_file".to_string())
+            .run()
+            .report();
+        assert!(report.items[0].skipped);
+        assert!(!report.items[0].success());
+        assert_eq!(report.failed().count(), 0);
+
+        let status = item
+            .with_when_cmd("test -f test_script.yaml".to_string())
+            .run();
+        assert!(status.status().unwrap().success());
+        assert_eq!(status.report().items[0].code, Some(0));
+    }
 }
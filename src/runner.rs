@@ -10,25 +10,379 @@
 //!assert!(result.status.success());
 //!```
 
+use std::error;
 use std::ffi::OsStr;
+use std::fmt;
 pub use std::io;
+use std::io::Read;
+use std::path::Path;
 pub use std::process::{
     Child, ChildStderr, ChildStdin, ChildStdout, Command, ExitStatus, Output, Stdio,
 };
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::shutdown;
+
+/// Default time between polite termination request and kill of process
+pub const DEFAULT_GRACE: Duration = Duration::from_secs(5);
+
+/// Interval of exit checks where exit of process can't be awaited
+#[cfg(not(unix))]
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Flag which cancels run, runners which have it terminate their processes
+/// when it's raised
+#[derive(Clone, Default)]
+pub struct Cancel {
+    state: Arc<CancelState>,
+}
+
+#[derive(Default)]
+struct CancelState {
+    raised: AtomicBool,
+    /// Runners which wait for raise of flag
+    waiters: Mutex<Vec<(u64, Sender<Event>)>>,
+}
+
+impl Cancel {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Raise flag and wake runners which wait for it
+    pub fn raise(&self) {
+        self.state.raised.store(true, Ordering::SeqCst);
+        for (_, waiter) in self.waiters().iter() {
+            let _ = waiter.send(Event::Cancelled);
+        }
+    }
+
+    pub fn is_raised(&self) -> bool {
+        self.state.raised.load(Ordering::SeqCst)
+    }
+
+    fn waiters(&self) -> MutexGuard<'_, Vec<(u64, Sender<Event>)>> {
+        self.state
+            .waiters
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Identifier of the next waiter of cancel flags
+static NEXT_WAITER: AtomicU64 = AtomicU64::new(0);
+
+/// Subscription to raise of cancel flags, it ends when it's dropped
+struct Waiter {
+    id: u64,
+    cancel: Vec<Cancel>,
+}
+
+impl Waiter {
+    fn new(cancel: &[Cancel], events: &Sender<Event>) -> Self {
+        let id = NEXT_WAITER.fetch_add(1, Ordering::SeqCst);
+        for flag in cancel {
+            flag.waiters().push((id, events.clone()));
+        }
+        Self {
+            id,
+            cancel: cancel.to_vec(),
+        }
+    }
+}
+
+impl Drop for Waiter {
+    fn drop(&mut self) {
+        for flag in self.cancel.iter() {
+            flag.waiters().retain(|(id, _)| *id != self.id);
+        }
+    }
+}
+
+/// Event which wakes runner waiting for its process
+enum Event {
+    Exited(io::Result<ExitStatus>),
+    Cancelled,
+}
 
 pub struct Runned {
     process: Child,
+    started: Instant,
+    timeout: Option<Duration>,
+    grace: Duration,
+    cancel: Vec<Cancel>,
+    #[cfg_attr(not(unix), allow(dead_code))]
+    process_group: bool,
+    /// Status of exited process, it's reaped when runned is dropped, see
+    /// [`Runned::signal_tree`]
+    status: Option<ExitStatus>,
+    /// Events of process exit and raised cancel flags
+    events: Option<(Sender<Event>, Receiver<Event>)>,
+    stdout_reader: Option<Capture>,
+    stderr_reader: Option<Capture>,
 }
 
 impl Runned {
     pub fn wait(&mut self) -> io::Result<ExitStatus> {
-        match self.process.try_wait() {
-            Ok(Some(status)) => Ok(status),
-            Ok(None) => self.process.wait(),
-            Err(e) => Err(e),
+        loop {
+            if let Some(status) = self.status {
+                return Ok(status);
+            }
+            self.next_event(None)?;
+        }
+    }
+
+    pub fn try_wait(&mut self) -> io::Result<Option<ExitStatus>> {
+        self.wait_timeout(Duration::ZERO)
+    }
+
+    /// Wait process exit not longer than `timeout`, `None` if it still runs
+    pub fn wait_timeout(&mut self, timeout: Duration) -> io::Result<Option<ExitStatus>> {
+        let deadline = Instant::now().checked_add(timeout);
+        loop {
+            let left = deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()));
+            self.next_event(left)?;
+            if self.status.is_some() || left == Some(Duration::ZERO) {
+                return Ok(self.status);
+            }
+        }
+    }
+
+    /// Wait the next event not longer than `timeout`, status of exited
+    /// process is saved
+    #[cfg(unix)]
+    fn next_event(&mut self, timeout: Option<Duration>) -> io::Result<()> {
+        if self.status.is_some() {
+            return Ok(());
+        }
+        let (_, events) = self.events();
+        let event = match timeout {
+            Some(timeout) => events.recv_timeout(timeout),
+            None => events.recv().map_err(|_| RecvTimeoutError::Disconnected),
+        };
+        match event {
+            Ok(Event::Exited(status)) => self.status = Some(status?),
+            Ok(Event::Cancelled) | Err(RecvTimeoutError::Timeout) => (),
+            Err(RecvTimeoutError::Disconnected) => {
+                return Err(io::Error::other("waiter of process exit is gone"))
+            }
+        }
+        Ok(())
+    }
+
+    /// Exit of process can't be awaited without its reaping here, so it's
+    /// checked every [`POLL_INTERVAL`], raised cancel flags wake waiter at once
+    #[cfg(not(unix))]
+    fn next_event(&mut self, timeout: Option<Duration>) -> io::Result<()> {
+        if self.status.is_some() {
+            return Ok(());
+        }
+        let wait = timeout.map_or(POLL_INTERVAL, |timeout| timeout.min(POLL_INTERVAL));
+        let _ = self.events().1.recv_timeout(wait);
+        self.status = self.process.try_wait()?;
+        Ok(())
+    }
+
+    /// Channel of events, thread which awaits exit of process starts with it
+    fn events(&mut self) -> &(Sender<Event>, Receiver<Event>) {
+        #[cfg(unix)]
+        let pid = self.process.id() as libc::pid_t;
+        self.events.get_or_insert_with(|| {
+            let (sender, receiver) = mpsc::channel();
+            #[cfg(unix)]
+            {
+                let exit = sender.clone();
+                thread::spawn(move || {
+                    let _ = exit.send(Event::Exited(wait_exit(pid)));
+                });
+            }
+            (sender, receiver)
+        })
+    }
+
+    /// Ask process to terminate (SIGTERM on Unix) and kill it if it still
+    /// runs after `grace` period
+    #[cfg(unix)]
+    pub fn terminate(&mut self, grace: Duration) -> io::Result<ExitStatus> {
+        self.interrupt(libc::SIGTERM, grace)
+    }
+
+    #[cfg(not(unix))]
+    pub fn terminate(&mut self, _grace: Duration) -> io::Result<ExitStatus> {
+        self.kill_tree()
+    }
+
+    /// Send `signal` to process, to its whole process group if process leads
+    /// one, and kill them if process still runs after `grace` period
+    #[cfg(unix)]
+    pub fn interrupt(&mut self, signal: i32, grace: Duration) -> io::Result<ExitStatus> {
+        if let Some(status) = self.try_wait()? {
+            return Ok(status);
+        }
+        self.signal_tree(signal)?;
+        self.wait_timeout(grace)?;
+        self.kill_tree()
+    }
+
+    /// Kill process with its process group if process leads one, processes
+    /// started in group of process are killed even if process already exited,
+    /// see [`Runner::set_process_group`]
+    #[cfg(unix)]
+    pub fn kill_tree(&mut self) -> io::Result<ExitStatus> {
+        self.signal_tree(libc::SIGKILL)?;
+        if self.status.is_none() {
+            self.process.kill()?;
+        }
+        self.wait()
+    }
+
+    #[cfg(not(unix))]
+    pub fn kill_tree(&mut self) -> io::Result<ExitStatus> {
+        self.process.kill()?;
+        let status = self.process.wait()?;
+        self.status = Some(status);
+        Ok(status)
+    }
+
+    /// Send `signal` to process group of process if it leads one or to
+    /// process if it still runs. Exited process isn't reaped until runned is
+    /// dropped, so neither its id nor id of its group is reused meanwhile
+    #[cfg(unix)]
+    fn signal_tree(&mut self, signal: i32) -> io::Result<()> {
+        let pid = self.process.id() as libc::pid_t;
+        let target = if self.process_group {
+            -pid
+        } else if self.try_wait()?.is_some() {
+            return Ok(());
+        } else if self.leads_group() {
+            -pid
+        } else {
+            pid
+        };
+        if unsafe { libc::kill(target, signal) } == 0 {
+            return Ok(());
+        }
+        match io::Error::last_os_error() {
+            // Group has no processes anymore
+            e if e.raw_os_error() == Some(libc::ESRCH) => Ok(()),
+            e => Err(e),
+        }
+    }
+
+    /// Wait process exit, process is terminated if it doesn't exit during
+    /// timeout of runner or if run is cancelled
+    pub fn wait_guarded(&mut self) -> io::Result<ExitStatus> {
+        // Deadline which is out of range of clock is never reached
+        let deadline = self
+            .timeout
+            .and_then(|timeout| self.started.checked_add(timeout));
+        let events = self.events().0.clone();
+        let _waiter = Waiter::new(&self.cancel, &events);
+        loop {
+            if let Some(status) = self.status {
+                return Ok(status);
+            }
+            if self.cancelled() {
+                self.stop(shutdown::received())?;
+                return Err(cancelled());
+            }
+            let left = deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()));
+            if let (Some(Duration::ZERO), Some(timeout)) = (left, self.timeout) {
+                self.terminate(self.grace)?;
+                return Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    format!("runned item timed out after {}s", timeout.as_secs_f64()),
+                ));
+            }
+            self.next_event(left)?;
+        }
+    }
+
+    /// Terminate cancelled process, `signal` which shuts down automatic is
+    /// forwarded to process. Terminal sends SIGINT to every process of its
+    /// foreground group, so it's forwarded only to process which leads own
+    /// group, other ones get grace period to exit
+    #[cfg(unix)]
+    fn stop(&mut self, signal: Option<i32>) -> io::Result<ExitStatus> {
+        match signal {
+            Some(libc::SIGINT) if !self.leads_group() => {
+                self.wait_timeout(self.grace)?;
+                self.kill_tree()
+            }
+            Some(signal) => self.interrupt(signal, self.grace),
+            None => self.terminate(self.grace),
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn stop(&mut self, _signal: Option<i32>) -> io::Result<ExitStatus> {
+        self.terminate(self.grace)
+    }
+
+    /// Process leads its own process group
+    #[cfg(unix)]
+    fn leads_group(&self) -> bool {
+        let pid = self.process.id() as libc::pid_t;
+        self.process_group || unsafe { libc::getpgid(pid) } == pid
+    }
+
+    fn cancelled(&self) -> bool {
+        self.cancel.iter().any(Cancel::is_raised)
+    }
+
+    /// Close piped stdin and start collecting of piped stdout and stderr in
+    /// background, it prevents blocking of process on full pipes
+    pub fn start_capture(&mut self) {
+        drop(self.process.stdin.take());
+        if let Some(stdout) = self.process.stdout.take() {
+            self.stdout_reader = Some(Capture::start(stdout));
+        }
+        if let Some(stderr) = self.process.stderr.take() {
+            self.stderr_reader = Some(Capture::start(stderr));
         }
     }
 
+    /// Wait process exit with collecting of its piped stdout and stderr,
+    /// see [`Runned::wait_guarded`]. Error of timed out or cancelled process
+    /// carries output collected before its termination, see
+    /// [`stopped_output`]
+    pub fn wait_with_output(&mut self) -> io::Result<Output> {
+        self.start_capture();
+
+        let (status, limit) = match self.wait_guarded() {
+            Ok(status) => (status, None),
+            Err(e) => match self.status {
+                // Processes started by stopped one can still hold its pipes
+                Some(status) => {
+                    let limit = Instant::now().checked_add(self.grace);
+                    let output = Output {
+                        status,
+                        stdout: finish(self.stdout_reader.take(), limit)?,
+                        stderr: finish(self.stderr_reader.take(), limit)?,
+                    };
+                    return Err(io::Error::new(
+                        e.kind(),
+                        Stopped {
+                            message: e.to_string(),
+                            output,
+                        },
+                    ));
+                }
+                None => return Err(e),
+            },
+        };
+
+        Ok(Output {
+            status,
+            stdout: finish(self.stdout_reader.take(), limit)?,
+            stderr: finish(self.stderr_reader.take(), limit)?,
+        })
+    }
+
     pub fn get_stdin(&mut self) -> Option<ChildStdin> {
         self.process.stdin.take()
     }
@@ -42,8 +396,136 @@ impl Runned {
     }
 }
 
+impl Drop for Runned {
+    /// Reap exited process
+    fn drop(&mut self) {
+        if self.status.is_some() {
+            let _ = self.process.wait();
+        }
+    }
+}
+
+/// Wait exit of child process `pid` without its reaping
+#[cfg(unix)]
+fn wait_exit(pid: libc::pid_t) -> io::Result<ExitStatus> {
+    use std::os::unix::process::ExitStatusExt;
+
+    loop {
+        let mut info: libc::siginfo_t = unsafe { std::mem::zeroed() };
+        let flags = libc::WEXITED | libc::WNOWAIT;
+        if unsafe { libc::waitid(libc::P_PID, pid as libc::id_t, &mut info, flags) } == 0 {
+            // Status in format of `waitpid`
+            let status = unsafe { info.si_status() };
+            return Ok(ExitStatus::from_raw(match info.si_code {
+                libc::CLD_EXITED => (status & 0xff) << 8,
+                libc::CLD_DUMPED => status | 0x80,
+                _ => status,
+            }));
+        }
+        let e = io::Error::last_os_error();
+        if e.kind() != io::ErrorKind::Interrupted {
+            return Err(e);
+        }
+    }
+}
+
+fn cancelled() -> io::Error {
+    io::Error::new(io::ErrorKind::Interrupted, "runned item cancelled")
+}
+
+/// Error of process which was stopped on timeout or cancellation with
+/// output collected before
+#[derive(Debug)]
+struct Stopped {
+    message: String,
+    output: Output,
+}
+
+impl fmt::Display for Stopped {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl error::Error for Stopped {}
+
+/// Output collected before process was stopped, `e` is error of
+/// [`Runned::wait_with_output`]
+pub fn stopped_output(e: &io::Error) -> Option<&Output> {
+    e.get_ref()?
+        .downcast_ref::<Stopped>()
+        .map(|stopped| &stopped.output)
+}
+
+/// Output of process which is collected in background
+struct Capture {
+    collected: Arc<Mutex<Vec<u8>>>,
+    done: Receiver<io::Result<()>>,
+    reader: JoinHandle<()>,
+}
+
+impl Capture {
+    fn start<R: Read + Send + 'static>(mut source: R) -> Self {
+        let collected = Arc::new(Mutex::new(vec![]));
+        let (end, done) = mpsc::channel();
+        let buffer = collected.clone();
+        let reader = thread::spawn(move || {
+            let mut chunk = [0; 8192];
+            let result = loop {
+                match source.read(&mut chunk) {
+                    Ok(0) => break Ok(()),
+                    Ok(read) => buffer
+                        .lock()
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .extend_from_slice(&chunk[..read]),
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => (),
+                    Err(e) => break Err(e),
+                }
+            };
+            let _ = end.send(result);
+        });
+        Self {
+            collected,
+            done,
+            reader,
+        }
+    }
+}
+
+/// Wait end of collected output until `limit`, output collected so far is
+/// taken if it doesn't end in time
+fn finish(capture: Option<Capture>, limit: Option<Instant>) -> io::Result<Vec<u8>> {
+    let capture = match capture {
+        Some(capture) => capture,
+        None => return Ok(vec![]),
+    };
+    let done = match limit {
+        Some(limit) => capture
+            .done
+            .recv_timeout(limit.saturating_duration_since(Instant::now()))
+            .ok(),
+        None => capture.done.recv().ok(),
+    };
+    if let Some(result) = done {
+        result?;
+        capture
+            .reader
+            .join()
+            .map_err(|_| io::Error::other("output reader panicked"))?;
+    }
+    let collected = capture
+        .collected
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+    Ok(collected.clone())
+}
+
 pub struct Runner {
     cmd: Command,
+    timeout: Option<Duration>,
+    grace: Duration,
+    cancel: Vec<Cancel>,
+    process_group: bool,
 }
 
 impl Runner {
@@ -54,7 +536,13 @@ impl Runner {
         cmd.stderr(Stdio::piped());
         cmd.args(args);
 
-        Runner { cmd }
+        Runner {
+            cmd,
+            timeout: None,
+            grace: DEFAULT_GRACE,
+            cancel: vec![],
+            process_group: false,
+        }
     }
 
     pub fn set_stdin<T: Into<Stdio>>(&mut self, stdin: T) -> &mut Self {
@@ -72,14 +560,104 @@ impl Runner {
         self
     }
 
+    pub fn set_env<K: AsRef<OsStr>, V: AsRef<OsStr>>(&mut self, key: K, val: V) -> &mut Self {
+        self.cmd.env(key, val);
+        self
+    }
+
+    pub fn set_env_remove<K: AsRef<OsStr>>(&mut self, key: K) -> &mut Self {
+        self.cmd.env_remove(key);
+        self
+    }
+
+    pub fn set_env_clear(&mut self) -> &mut Self {
+        self.cmd.env_clear();
+        self
+    }
+
+    pub fn set_cwd<P: AsRef<Path>>(&mut self, dir: P) -> &mut Self {
+        self.cmd.current_dir(dir);
+        self
+    }
+
+    /// Set limit of run time, after it process is terminated
+    pub fn set_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Set time between termination request and kill of timed out or
+    /// cancelled process
+    pub fn set_grace(&mut self, grace: Duration) -> &mut Self {
+        self.grace = grace;
+        self
+    }
+
+    /// Start process in new process group, termination and kill of process
+    /// address the whole group so processes started by it don't outlive it
+    pub fn set_process_group(&mut self) -> &mut Self {
+        #[cfg(unix)]
+        {
+            use std::os::unix::process::CommandExt;
+
+            self.cmd.process_group(0);
+        }
+        self.process_group = true;
+        self
+    }
+
+    /// Add flag which terminates running process when it's raised, process
+    /// isn't started if any flag is already raised
+    pub fn add_cancel(&mut self, cancel: Cancel) -> &mut Self {
+        self.cancel.push(cancel);
+        self
+    }
+
     pub fn run(&mut self) -> io::Result<Output> {
-        self.cmd.output()
+        if self.timeout.is_none() && self.cancel.is_empty() {
+            return self.cmd.output();
+        }
+        self.spawn()?.wait_with_output()
     }
 
-    pub fn run_async(&mut self) -> Runned {
-        Runned {
-            process: self.cmd.spawn().expect("failed to execute process"),
+    /// Wait `duration` before the next run, it's interrupted by raised
+    /// cancel flag
+    pub fn sleep(&self, duration: Duration) -> io::Result<()> {
+        let (events, receiver) = mpsc::channel();
+        let _waiter = Waiter::new(&self.cancel, &events);
+        if self.cancelled() {
+            return Err(cancelled());
+        }
+        match receiver.recv_timeout(duration) {
+            Ok(_) => Err(cancelled()),
+            Err(_) => Ok(()),
+        }
+    }
+
+    fn cancelled(&self) -> bool {
+        self.cancel.iter().any(Cancel::is_raised)
+    }
+
+    pub fn spawn(&mut self) -> io::Result<Runned> {
+        if self.cancelled() {
+            return Err(cancelled());
         }
+        Ok(Runned {
+            process: self.cmd.spawn()?,
+            started: Instant::now(),
+            timeout: self.timeout,
+            grace: self.grace,
+            cancel: self.cancel.clone(),
+            process_group: self.process_group,
+            status: None,
+            events: None,
+            stdout_reader: None,
+            stderr_reader: None,
+        })
+    }
+
+    pub fn run_async(&mut self) -> Runned {
+        self.spawn().expect("failed to execute process")
     }
 }
 
@@ -139,6 +717,193 @@ mod tests {
         assert!(!result.success());
     }
 
+    #[test]
+    fn env_and_cwd() {
+        let result = Runner::new("sh", vec!["-c", "echo $AUTOMATIC_TEST; pwd"])
+            .set_env("AUTOMATIC_TEST", "value")
+            .set_cwd("/")
+            .run()
+            .expect("failed to execute process");
+        assert_eq!(String::from_utf8_lossy(&result.stdout), "value\n/\n");
+
+        let result = Runner::new("sh", vec!["-c", "echo ${HOME:-unset}"])
+            .set_env_remove("HOME")
+            .run()
+            .expect("failed to execute process");
+        assert_eq!(String::from_utf8_lossy(&result.stdout), "unset\n");
+
+        let result = Runner::new("/usr/bin/env", vec![])
+            .set_env_clear()
+            .set_env("ONLY", "one")
+            .run()
+            .expect("failed to execute process");
+        assert_eq!(String::from_utf8_lossy(&result.stdout), "ONLY=one\n");
+    }
+
+    #[test]
+    fn timeout() {
+        let result = Runner::new("sh", vec!["-c", "echo done"])
+            .set_timeout(Duration::from_secs(5))
+            .run()
+            .expect("failed to execute process");
+        assert!(result.status.success());
+        assert_eq!(String::from_utf8_lossy(&result.stdout), "done\n");
+
+        let start = Instant::now();
+        let result = Runner::new("sleep", vec!["10"])
+            .set_timeout(Duration::from_millis(100))
+            .run();
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::TimedOut);
+        assert!(start.elapsed() < Duration::from_secs(5));
+
+        let start = Instant::now();
+        let result = Runner::new("sh", vec!["-c", "trap '' TERM; sleep 10"])
+            .set_timeout(Duration::from_millis(100))
+            .set_grace(Duration::from_millis(100))
+            .run();
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::TimedOut);
+        assert!(start.elapsed() < Duration::from_secs(5));
+
+        // Output of stopped process is kept, background sleep which holds
+        // pipe after kill of shell doesn't delay result beyond grace period
+        let start = Instant::now();
+        let result = Runner::new("sh", vec!["-c", "echo started; sleep 10 & sleep 10"])
+            .set_timeout(Duration::from_millis(200))
+            .set_grace(Duration::from_millis(100))
+            .run();
+        let e = result.unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::TimedOut);
+        assert_eq!(stopped_output(&e).unwrap().stdout, b"started\n");
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn cancel() {
+        let cancel = Cancel::new();
+
+        let result = Runner::new("true", vec![])
+            .add_cancel(cancel.clone())
+            .run()
+            .expect("failed to execute process");
+        assert!(result.status.success());
+
+        let flag = cancel.clone();
+        let handle = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(100));
+            flag.raise();
+        });
+        let start = Instant::now();
+        let result = Runner::new("sleep", vec!["10"]).add_cancel(cancel).run();
+        handle.join().unwrap();
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::Interrupted);
+        assert!(start.elapsed() < Duration::from_secs(5));
+
+        let cancel = Cancel::new();
+        cancel.raise();
+        let result = Runner::new("true", vec![]).add_cancel(cancel).spawn();
+        assert_eq!(result.err().unwrap().kind(), io::ErrorKind::Interrupted);
+
+        let cancel = Cancel::new();
+        let mut runner = Runner::new("true", vec![]);
+        runner.add_cancel(cancel.clone());
+        assert!(runner.sleep(Duration::from_millis(20)).is_ok());
+        cancel.raise();
+        let start = Instant::now();
+        let result = runner.sleep(Duration::from_secs(10));
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::Interrupted);
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn process_group() {
+        // Background sleep keeps stdout open until the whole group is killed
+        let mut runned = Runner::new("sh", vec!["-c", "sleep 10 & echo started; wait"])
+            .set_process_group()
+            .spawn()
+            .expect("failed to execute process");
+        let mut output = runned.get_stdout().unwrap();
+        let mut line = [0; 8];
+        output.read_exact(&mut line).expect("failed in read");
+        assert_eq!(&line, b"started\n");
+
+        let start = Instant::now();
+        assert!(!runned.kill_tree().expect("failed to kill").success());
+        output.read_to_end(&mut vec![]).expect("failed in read");
+        assert!(start.elapsed() < Duration::from_secs(5));
+
+        // Shell exits on SIGTERM, sleep ignores it and is killed after grace
+        let mut runned = Runner::new(
+            "sh",
+            vec![
+                "-c",
+                "trap '' TERM; sleep 10 & trap - TERM; echo started; wait",
+            ],
+        )
+        .set_process_group()
+        .spawn()
+        .expect("failed to execute process");
+        let mut output = runned.get_stdout().unwrap();
+        output.read_exact(&mut line).expect("failed in read");
+
+        let start = Instant::now();
+        runned
+            .terminate(Duration::from_millis(100))
+            .expect("failed to terminate");
+        output.read_to_end(&mut vec![]).expect("failed in read");
+        assert!(start.elapsed() < Duration::from_secs(5));
+        // Shell exits at once, its background sleep outlives it in the group
+        let mut runned = Runner::new("sh", vec!["-c", "sleep 10 & echo started"])
+            .set_process_group()
+            .spawn()
+            .expect("failed to execute process");
+        let mut output = runned.get_stdout().unwrap();
+        output.read_exact(&mut line).expect("failed in read");
+        assert!(runned.wait().expect("failed to wait").success());
+
+        // Exited leader keeps id of group until runned is dropped
+        let pid = runned.process.id() as libc::pid_t;
+        assert_eq!(unsafe { libc::kill(pid, 0) }, 0);
+
+        let start = Instant::now();
+        assert!(runned.kill_tree().expect("failed to kill").success());
+        output.read_to_end(&mut vec![]).expect("failed in read");
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert!(runned.kill_tree().expect("failed to kill").success());
+
+        drop(runned);
+        assert_eq!(unsafe { libc::kill(pid, 0) }, -1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn forward_sigint() {
+        use std::os::unix::process::ExitStatusExt;
+
+        let script = "trap 'exit 3' INT; echo started; while true; do sleep 0.05; done";
+
+        // Process of automatic group gets SIGINT from terminal, not forwarded
+        let mut runned = Runner::new("sh", vec!["-c", script])
+            .set_grace(Duration::from_millis(200))
+            .spawn()
+            .expect("failed to execute process");
+        let mut output = runned.get_stdout().unwrap();
+        let mut line = [0; 8];
+        output.read_exact(&mut line).expect("failed in read");
+        let status = runned.stop(Some(libc::SIGINT)).expect("failed to stop");
+        assert_eq!(status.signal(), Some(libc::SIGKILL));
+
+        let mut runned = Runner::new("sh", vec!["-c", script])
+            .set_grace(Duration::from_secs(5))
+            .set_process_group()
+            .spawn()
+            .expect("failed to execute process");
+        let mut output = runned.get_stdout().unwrap();
+        output.read_exact(&mut line).expect("failed in read");
+        let status = runned.stop(Some(libc::SIGINT)).expect("failed to stop");
+        assert_eq!(status.code(), Some(3));
+    }
+
     #[test]
     fn std_in_out() {
         let mut r_async = Runner::new("cat", vec![]).run_async();
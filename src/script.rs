@@ -1,4 +1,5 @@
-//! Yaml script parser and run items list collector
+//! Script parser and run items list collector, script is yaml, json or toml
+//! file
 //!
 //! # Example
 //!```
@@ -22,62 +23,832 @@
 //!}
 //!```
 
-use serde::{Deserialize, Serialize};
+use schemars::gen::SchemaGenerator;
+use schemars::schema::{RootSchema, Schema};
+use schemars::{schema_for, JsonSchema};
+use serde::de::value::{MapAccessDeserializer, StringDeserializer};
+use serde::de::{self, DeserializeOwned, DeserializeSeed, MapAccess};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
 use std::fs::File;
 use std::io::prelude::*;
-use std::path::PathBuf;
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
 
+use crate::document::Document;
+use crate::error::Error;
+use crate::format::Format;
+use crate::graph_list::GraphList;
 use crate::interactive_list::InteractiveList;
+use crate::item::{Context, Item, RunIn};
+use crate::parallel_list::ParallelList;
+use crate::pipeline_list::PipelineList;
+use crate::plan::Visitor;
 use crate::promiscuous_list::PromiscuousList;
+use crate::runitem::{Defaults, RunItem};
 use crate::silent_list::SilentList;
+use crate::vars::Vars;
 
 use crate::run::{Run, RunStatus};
 
-#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, PartialEq, Serialize, Deserialize, JsonSchema, Clone)]
 pub enum List {
     Interactive(InteractiveList),
     Promiscuous(PromiscuousList),
     Silent(SilentList),
+    Parallel(ParallelList),
+    Pipeline(PipelineList),
+    Graph(GraphList),
+}
+
+impl List {
+    /// Names of list kinds as they are written in script
+    pub const KINDS: [&'static str; 6] = [
+        "Interactive",
+        "Promiscuous",
+        "Silent",
+        "Parallel",
+        "Pipeline",
+        "Graph",
+    ];
+
+    /// Walk list tree in run order, run items are passed to `visitor` with
+    /// applied defaults of their lists, not resolved includes are skipped
+    pub fn accept<V: Visitor + ?Sized>(&self, visitor: &mut V) {
+        self.accept_in(&Defaults::new(), visitor)
+    }
+
+    fn accept_in<V: Visitor + ?Sized>(&self, parent: &Defaults, visitor: &mut V) {
+        let (items, defaults) = match self {
+            List::Interactive(list) => (list.items(), list.defaults()),
+            List::Promiscuous(list) => (list.items(), list.defaults()),
+            List::Silent(list) => (list.items(), list.defaults()),
+            List::Parallel(list) => (list.items(), list.defaults()),
+            List::Pipeline(list) => {
+                let defaults = list.defaults().inherit(parent);
+                visitor.enter_list(self, &defaults);
+                for item in list.items() {
+                    visitor.visit_item(&item.inherit(&defaults));
+                }
+                visitor.leave_list(self);
+                return;
+            }
+            List::Graph(list) => {
+                let defaults = list.defaults().inherit(parent);
+                let order = list
+                    .order()
+                    .unwrap_or_else(|_| (0..list.items().len()).collect());
+                visitor.enter_list(self, &defaults);
+                for index in order {
+                    visitor.visit_item(&list.items()[index].inherit(&defaults));
+                }
+                visitor.leave_list(self);
+                return;
+            }
+        };
+
+        let defaults = defaults.inherit(parent);
+        visitor.enter_list(self, &defaults);
+        for item in items {
+            match item {
+                Item::Run(item) => visitor.visit_item(&item.inherit(&defaults)),
+                Item::List(list) => list.accept_in(&defaults, visitor),
+                Item::Include(_) => {}
+            }
+        }
+        visitor.leave_list(self);
+    }
+
+    /// Check dependencies of graph lists in list tree
+    pub fn validate(&self) -> Result<(), Error> {
+        let mut check = GraphCheck { error: None };
+        self.accept(&mut check);
+        match check.error {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+
+    /// Defaults of list for its items and nested lists
+    pub fn defaults(&self) -> &Defaults {
+        match self {
+            List::Interactive(list) => list.defaults(),
+            List::Promiscuous(list) => list.defaults(),
+            List::Silent(list) => list.defaults(),
+            List::Parallel(list) => list.defaults(),
+            List::Pipeline(list) => list.defaults(),
+            List::Graph(list) => list.defaults(),
+        }
+    }
+
+    /// Items of list which can contain nested lists
+    fn items_mut(&mut self) -> Option<&mut Vec<Item>> {
+        match self {
+            List::Interactive(list) => Some(list.items_mut()),
+            List::Promiscuous(list) => Some(list.items_mut()),
+            List::Silent(list) => Some(list.items_mut()),
+            List::Parallel(list) => Some(list.items_mut()),
+            List::Pipeline(_) | List::Graph(_) => None,
+        }
+    }
+
+    /// Substitute variables into every item of list and its nested lists
+    pub(crate) fn interpolate(&mut self, vars: &Vars) -> Result<(), Error> {
+        match self {
+            List::Interactive(list) => list.interpolate(vars),
+            List::Promiscuous(list) => list.interpolate(vars),
+            List::Silent(list) => list.interpolate(vars),
+            List::Parallel(list) => list.interpolate(vars),
+            List::Pipeline(list) => list.interpolate(vars),
+            List::Graph(list) => list.interpolate(vars),
+        }
+    }
+}
+
+/// Visitor which keeps the first dependency error of graph lists
+struct GraphCheck {
+    error: Option<Error>,
+}
+
+impl Visitor for GraphCheck {
+    fn enter_list(&mut self, list: &List, _defaults: &Defaults) {
+        if let List::Graph(list) = list {
+            if let (None, Err(e)) = (&self.error, list.order()) {
+                self.error = Some(e);
+            }
+        }
+    }
+
+    fn visit_item(&mut self, _item: &RunItem) {}
+}
+
+impl RunIn for List {
+    fn run_in(&self, ctx: &Context) -> Box<dyn RunStatus> {
+        match self {
+            List::Interactive(list) => list.run_in(ctx),
+            List::Promiscuous(list) => list.run_in(ctx),
+            List::Silent(list) => list.run_in(ctx),
+            List::Parallel(list) => list.run_in(ctx),
+            List::Pipeline(list) => list.run_in(ctx),
+            List::Graph(list) => list.run_in(ctx),
+        }
+    }
 }
 
 impl Run for List {
     fn run(&self) -> Box<dyn RunStatus> {
+        self.run_in(&Context::default())
+    }
+}
+
+impl fmt::Display for List {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            List::Interactive(list) => list.run(),
-            List::Promiscuous(list) => list.run(),
-            List::Silent(list) => list.run(),
+            List::Interactive(_) => write!(f, "Interactive list"),
+            List::Promiscuous(_) => write!(f, "Promiscuous list"),
+            List::Silent(_) => write!(f, "Silent list"),
+            List::Parallel(_) => write!(f, "Parallel list"),
+            List::Pipeline(_) => write!(f, "Pipeline list"),
+            List::Graph(_) => write!(f, "Graph list"),
+        }
+    }
+}
+
+/// Schema of list flattened into other fields, variants of list allow these
+/// fields
+fn flattened_list(gen: &mut SchemaGenerator) -> Schema {
+    let mut schema = List::json_schema(gen).into_object();
+    for variant in schema.subschemas().one_of.iter_mut().flatten() {
+        if let Schema::Object(variant) = variant {
+            variant.object().additional_properties = None;
+        }
+    }
+    Schema::Object(schema)
+}
+
+/// Fields of object which holds run list, the list is read in place, so its
+/// errors keep their position
+fn deserialize_with_list<'de, D, F>(deserializer: D) -> Result<(F, Option<List>), D::Error>
+where
+    D: Deserializer<'de>,
+    F: Deserialize<'de>,
+{
+    deserializer.deserialize_map(ListHolderVisitor(PhantomData))
+}
+
+/// Error of object which has no run list
+fn no_list<E: de::Error>() -> E {
+    E::custom(format!(
+        "no list, expected one of {}",
+        List::KINDS.join(", ")
+    ))
+}
+
+struct ListHolderVisitor<F>(PhantomData<F>);
+
+impl<'de, F: Deserialize<'de>> de::Visitor<'de> for ListHolderVisitor<F> {
+    type Value = (F, Option<List>);
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "run list with its fields")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<Self::Value, A::Error> {
+        let mut list = None;
+        let fields = F::deserialize(MapAccessDeserializer::new(ListFields {
+            map,
+            list: &mut list,
+        }))?;
+        Ok((fields, list.map(|(_, list)| list)))
+    }
+}
+
+/// Fields of `map` without run list which is taken out to `list` with its
+/// kind
+struct ListFields<'a, A> {
+    map: A,
+    list: &'a mut Option<(String, List)>,
+}
+
+impl<'de, 'a, A: MapAccess<'de>> MapAccess<'de> for ListFields<'a, A> {
+    type Error = A::Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        mut seed: K,
+    ) -> Result<Option<K::Value>, A::Error> {
+        loop {
+            let key = match self.list {
+                Some((kind, _)) => self.map.next_key_seed(ListKey::after(kind, seed))?,
+                None => self.map.next_key_seed(ListKey::new(seed))?,
+            };
+            match key {
+                Some(Key::List(kind, unused)) => {
+                    let list = self.map.next_value_seed(ListBody(&kind))?;
+                    *self.list = Some((kind, list));
+                    seed = unused;
+                }
+                Some(Key::Field(key)) => return Ok(Some(key)),
+                None => return Ok(None),
+            }
         }
     }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, A::Error> {
+        self.map.next_value_seed(seed)
+    }
+}
+
+/// Key of object which can hold run list: name of list with seed of other
+/// fields which is not used, or other field read by the seed
+enum Key<K, V> {
+    List(String, K),
+    Field(V),
+}
+
+/// Seed of key of object which can hold run list, `seed` reads other keys,
+/// unknown list is reported at the key
+struct ListKey<'a, K> {
+    seed: K,
+    list: Option<&'a str>,
 }
 
+impl<'a, K> ListKey<'a, K> {
+    fn new(seed: K) -> Self {
+        Self { seed, list: None }
+    }
+
+    /// Key of object which already has `list`
+    fn after(list: &'a str, seed: K) -> Self {
+        Self {
+            seed,
+            list: Some(list),
+        }
+    }
+}
+
+impl<'de, 'a, K: DeserializeSeed<'de>> DeserializeSeed<'de> for ListKey<'a, K> {
+    type Value = Key<K, K::Value>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_str(self)
+    }
+}
+
+impl<'de, 'a, K: DeserializeSeed<'de>> de::Visitor<'de> for ListKey<'a, K> {
+    type Value = Key<K, K::Value>;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "field name")
+    }
+
+    fn visit_str<E: de::Error>(self, key: &str) -> Result<Self::Value, E> {
+        if !key.starts_with(char::is_uppercase) {
+            return self
+                .seed
+                .deserialize(StringDeserializer::new(key.to_string()))
+                .map(Key::Field);
+        }
+        if !List::KINDS.contains(&key) {
+            return Err(E::custom(format!(
+                "unknown list `{}`, expected one of {}",
+                key,
+                List::KINDS.join(", ")
+            )));
+        }
+        if let Some(list) = self.list {
+            return Err(E::custom(format!(
+                "more than one list: `{}` and `{}`",
+                list, key
+            )));
+        }
+        Ok(Key::List(key.to_string(), self.seed))
+    }
+}
+
+/// Seed of list of kind, the list is read in place
+struct ListBody<'a>(&'a str);
+
+impl<'de, 'a> DeserializeSeed<'de> for ListBody<'a> {
+    type Value = List;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<List, D::Error> {
+        match self.0 {
+            "Interactive" => InteractiveList::deserialize(deserializer).map(List::Interactive),
+            "Promiscuous" => PromiscuousList::deserialize(deserializer).map(List::Promiscuous),
+            "Silent" => SilentList::deserialize(deserializer).map(List::Silent),
+            "Parallel" => ParallelList::deserialize(deserializer).map(List::Parallel),
+            "Pipeline" => PipelineList::deserialize(deserializer).map(List::Pipeline),
+            "Graph" => GraphList::deserialize(deserializer).map(List::Graph),
+            kind => Err(de::Error::unknown_variant(kind, &List::KINDS)),
+        }
+    }
+}
+
+/// Content of script file: run list with variables used by its items
+#[derive(Serialize, JsonSchema)]
+struct ScriptFile {
+    #[serde(default, skip_serializing_if = "Vars::is_empty")]
+    vars: Vars,
+    /// Items which run after run list even if it fails or is interrupted
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    cleanup: Vec<Item>,
+    #[serde(flatten)]
+    #[schemars(schema_with = "flattened_list")]
+    list: List,
+}
+
+/// Content of script file with named targets, script without targets has
+/// the only run list
+#[derive(Serialize, JsonSchema)]
+struct TargetsFile {
+    #[serde(default, skip_serializing_if = "Vars::is_empty")]
+    vars: Vars,
+    #[serde(flatten)]
+    targets: Targets,
+}
+
+/// Content of script file which has either run list or named targets
+enum ScriptContent {
+    List(ScriptFile),
+    Targets(TargetsFile),
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ScriptFields {
+    #[serde(default)]
+    vars: Vars,
+    #[serde(default)]
+    cleanup: Vec<Item>,
+    #[serde(default)]
+    targets: Option<BTreeMap<String, Target>>,
+    #[serde(default)]
+    default: Option<String>,
+}
+
+impl<'de> Deserialize<'de> for ScriptContent {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let (fields, list): (ScriptFields, _) = deserialize_with_list(deserializer)?;
+        match (fields.targets, list) {
+            (Some(targets), None) if fields.cleanup.is_empty() => {
+                Ok(ScriptContent::Targets(TargetsFile {
+                    vars: fields.vars,
+                    targets: Targets {
+                        targets,
+                        default: fields.default,
+                    },
+                }))
+            }
+            (Some(_), _) => Err(de::Error::custom(
+                "script with targets has run list or cleanup out of targets",
+            )),
+            (None, Some(_)) if fields.default.is_some() => Err(de::Error::custom(
+                "script without targets has `default` target",
+            )),
+            (None, Some(list)) => Ok(ScriptContent::List(ScriptFile {
+                vars: fields.vars,
+                cleanup: fields.cleanup,
+                list,
+            })),
+            (None, None) => Err(no_list()),
+        }
+    }
+}
+
+/// Script file has either run list or named targets
+#[allow(dead_code)]
+#[derive(JsonSchema)]
+#[serde(untagged)]
+#[schemars(rename = "Script")]
+enum ScriptSchema {
+    Script(ScriptFile),
+    #[schemars(schema_with = "targets_file")]
+    Targets(TargetsFile),
+}
+
+/// Schema of script file which has targets
+fn targets_file(gen: &mut SchemaGenerator) -> Schema {
+    let mut schema = TargetsFile::json_schema(gen).into_object();
+    schema.object().required.insert("targets".to_string());
+    Schema::Object(schema)
+}
+
+/// Named run list of script
+#[derive(Debug, PartialEq, Serialize, JsonSchema, Clone)]
+pub struct Target {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+    /// Items which run after run list of target, see [`Script::cleanup`]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    cleanup: Vec<Item>,
+    #[serde(flatten)]
+    #[schemars(schema_with = "flattened_list")]
+    list: List,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TargetFields {
+    #[serde(default)]
+    description: Option<String>,
+    #[serde(default)]
+    cleanup: Vec<Item>,
+}
+
+impl<'de> Deserialize<'de> for Target {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let (fields, list): (TargetFields, _) = deserialize_with_list(deserializer)?;
+        Ok(Self {
+            description: fields.description,
+            cleanup: fields.cleanup,
+            list: list.ok_or_else(no_list)?,
+        })
+    }
+}
+
+impl Target {
+    pub fn new(list: List) -> Self {
+        Self {
+            description: None,
+            cleanup: vec![],
+            list,
+        }
+    }
+
+    pub fn with_description<S: Into<String>>(mut self, description: S) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    pub fn with_cleanup(mut self, cleanup: Vec<Item>) -> Self {
+        self.cleanup = cleanup;
+        self
+    }
+
+    pub fn description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+
+    pub fn cleanup(&self) -> &[Item] {
+        &self.cleanup
+    }
+
+    pub fn list(&self) -> &List {
+        &self.list
+    }
+}
+
+/// Targets of script with name of target which runs if no one is selected
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize, JsonSchema, Clone)]
+pub struct Targets {
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    targets: BTreeMap<String, Target>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    default: Option<String>,
+}
+
+impl Targets {
+    /// Name of default target, the only target is default one
+    pub fn default_target(&self) -> Option<&str> {
+        match &self.default {
+            Some(name) => Some(name),
+            None if self.targets.len() == 1 => self.targets.keys().next().map(String::as_str),
+            None => None,
+        }
+    }
+
+    /// Targets in order of their names
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Target)> {
+        self.targets
+            .iter()
+            .map(|(name, target)| (name.as_str(), target))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.targets.is_empty()
+    }
+
+    /// Take target with `name` or default one
+    fn select(mut self, name: Option<&str>) -> Result<Target, Error> {
+        let available: Vec<String> = self.targets.keys().cloned().collect();
+        let name = match name.or_else(|| self.default_target()) {
+            Some(name) => name.to_string(),
+            None => {
+                return Err(Error::UnknownTarget {
+                    name: None,
+                    targets: available,
+                })
+            }
+        };
+        match self.targets.remove(&name) {
+            Some(target) => Ok(target),
+            None => Err(Error::UnknownTarget {
+                name: Some(name),
+                targets: available,
+            }),
+        }
+    }
+}
+
+#[derive(Clone)]
 pub struct Script {
     path: PathBuf,
+    /// Content of script which is not read from file
+    content: Option<String>,
+    vars: Vars,
+    target: Option<String>,
+    format: Option<Format>,
 }
 
 impl Script {
     pub fn new(path: PathBuf) -> Self {
-        Self { path }
+        Self {
+            path,
+            content: None,
+            vars: Vars::new(),
+            target: None,
+            format: None,
+        }
+    }
+
+    /// Script with `content` instead of file, its path is `-` and relative
+    /// paths of its includes are resolved against current directory
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(content: &str) -> Self {
+        Self {
+            content: Some(content.to_string()),
+            ..Self::new(PathBuf::from("-"))
+        }
+    }
+
+    /// Script with content read from `reader` like stdin, see
+    /// [`Script::from_str`]
+    pub fn from_reader<R: Read>(mut reader: R) -> Result<Self, Error> {
+        let mut content = String::new();
+        reader
+            .read_to_string(&mut content)
+            .map_err(|source| Error::ScriptRead {
+                path: PathBuf::from("-"),
+                source,
+            })?;
+        Ok(Self::from_str(&content))
+    }
+
+    /// Parse script in `format` instead of format detected by extension
+    pub fn with_format(mut self, format: Format) -> Self {
+        self.format = Some(format);
+        self
+    }
+
+    /// Format of script, detected by extension of path if not set
+    pub fn format(&self) -> Format {
+        self.format.unwrap_or_else(|| Format::from_path(&self.path))
+    }
+
+    /// Override values of script variables
+    pub fn with_vars(mut self, vars: Vars) -> Self {
+        self.vars = vars;
+        self
+    }
+
+    /// Select target of script with targets instead of default one
+    pub fn with_target<S: Into<String>>(mut self, target: S) -> Self {
+        self.target = Some(target.into());
+        self
     }
 
-    pub fn parse(&self) -> Result<List, String> {
+    /// JSON Schema of script file
+    pub fn schema() -> RootSchema {
+        schema_for!(ScriptSchema)
+    }
+
+    /// Path of script, `-` for script which is not read from file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub(crate) fn read(&self) -> Result<String, Error> {
+        if let Some(content) = &self.content {
+            return Ok(content.clone());
+        }
         if !self.path.exists() {
-            return Err(format!(
-                "Script: {} doesn't exist",
-                self.path.to_str().unwrap()
-            ));
+            return Err(Error::ScriptNotFound(self.path.clone()));
         }
 
-        let mut f = File::open(self.path.clone())
-            .or_else(|e| Err(format!("fail to open script with error: {}", e)))?;
+        let read_error = |source| Error::ScriptRead {
+            path: self.path.clone(),
+            source,
+        };
+
+        let mut f = File::open(self.path.clone()).map_err(read_error)?;
 
         let mut s = String::new();
-        f.read_to_string(&mut s)
-            .or_else(|e| Err(format!("fail to read script with error: {}", e)))?;
+        f.read_to_string(&mut s).map_err(read_error)?;
+        Ok(s)
+    }
+
+    /// Set path of script to parse error
+    fn with_path(&self, e: Error) -> Error {
+        match e {
+            Error::Parse {
+                path: None,
+                location,
+                message,
+            } => Error::Parse {
+                path: Some(self.path.clone()),
+                location,
+                message,
+            },
+            e => e,
+        }
+    }
+
+    pub(crate) fn decode<T: DeserializeOwned>(&self, s: &str) -> Result<T, Error> {
+        self.format().from_str(s).map_err(|e| self.with_path(e))
+    }
+
+    /// Parse errors of every item and list of script `s`, error of item
+    /// doesn't stop parse of other items
+    pub(crate) fn parse_errors(&self, s: &str) -> Result<Vec<String>, Error> {
+        let document = Document::load(self.format(), s).map_err(|e| self.with_path(e))?;
+        Ok(document.errors::<ScriptContent>())
+    }
+
+    /// Targets of script, empty for script without targets
+    pub fn targets(&self) -> Result<Targets, Error> {
+        let s = self.read()?;
+        match self.decode(&s)? {
+            ScriptContent::List(_) => Ok(Targets::default()),
+            ScriptContent::Targets(script) => Ok(script.targets),
+        }
+    }
+
+    /// Encode script in other format, variables, targets and includes are
+    /// kept as is
+    pub fn convert(&self, format: Format) -> Result<String, Error> {
+        let s = self.read()?;
+        let encoded = match self.decode(&s)? {
+            ScriptContent::List(script) => format.to_string(&script),
+            ScriptContent::Targets(script) => format.to_string(&script),
+        };
+        encoded.map_err(|e| self.with_path(e))
+    }
+
+    /// Parse run list of script or its selected target, included scripts
+    /// are parsed with variables of including one
+    pub fn parse(&self) -> Result<List, Error> {
+        let list = self.parse_unvalidated()?;
+        list.validate()?;
+        Ok(list)
+    }
+
+    /// Parse run list without check of graph lists
+    pub(crate) fn parse_unvalidated(&self) -> Result<List, Error> {
+        self.parse_included(&[])
+    }
+
+    /// Items which run after run list of script or its selected target even
+    /// if it fails or is interrupted, they run as promiscuous list with
+    /// defaults of the run list, cleanup items of included scripts are not
+    /// run
+    pub fn cleanup(&self) -> Result<List, Error> {
+        let list = self.cleanup_unvalidated()?;
+        list.validate()?;
+        Ok(list)
+    }
+
+    /// Cleanup items without check of graph lists
+    pub(crate) fn cleanup_unvalidated(&self) -> Result<List, Error> {
+        let (vars, list, cleanup) = self.decode_lists()?;
+        let mut list = List::Promiscuous(
+            PromiscuousList::from_items(cleanup).with_defaults(list.defaults().clone()),
+        );
+
+        list.interpolate(&vars).map_err(|e| self.with_path(e))?;
+        self.resolve_includes(&mut list, &vars, std::slice::from_ref(&self.path))?;
+        Ok(list)
+    }
+
+    /// Parse script included by scripts of `chain`
+    fn parse_included(&self, chain: &[PathBuf]) -> Result<List, Error> {
+        let (vars, mut list, _) = self.decode_lists()?;
+
+        list.interpolate(&vars).map_err(|e| self.with_path(e))?;
+
+        let mut chain = chain.to_vec();
+        chain.push(self.path.clone());
+        self.resolve_includes(&mut list, &vars, &chain)?;
+        Ok(list)
+    }
 
-        match serde_yaml::from_str(&s) {
-            Ok(entity) => Ok(entity),
-            Err(e) => Err(e.to_string()),
+    /// Variables, run list and cleanup items of script or its selected
+    /// target, variables of script are overridden by own ones
+    fn decode_lists(&self) -> Result<(Vars, List, Vec<Item>), Error> {
+        let s = self.read()?;
+
+        let (mut vars, list, cleanup) = match self.decode(&s)? {
+            ScriptContent::List(_) if self.target.is_some() => {
+                return Err(Error::UnknownTarget {
+                    name: self.target.clone(),
+                    targets: vec![],
+                });
+            }
+            ScriptContent::List(script) => (script.vars, script.list, script.cleanup),
+            ScriptContent::Targets(script) => {
+                let target = script.targets.select(self.target.as_deref())?;
+                (script.vars, target.list, target.cleanup)
+            }
+        };
+        vars.extend(self.vars.clone());
+        Ok((vars, list, cleanup))
+    }
+
+    /// Replace includes in list tree by lists of included scripts
+    fn resolve_includes(
+        &self,
+        list: &mut List,
+        vars: &Vars,
+        chain: &[PathBuf],
+    ) -> Result<(), Error> {
+        let items = match list.items_mut() {
+            Some(items) => items,
+            None => return Ok(()),
+        };
+        let dir = self.path.parent().unwrap_or_else(|| Path::new(""));
+        for item in items.iter_mut() {
+            let include = match item {
+                Item::List(list) => {
+                    self.resolve_includes(list, vars, chain)?;
+                    continue;
+                }
+                Item::Include(include) => include.script(dir, vars),
+                Item::Run(_) => continue,
+            };
+
+            let mut included = chain.to_vec();
+            included.push(include.path.clone());
+            if chain.iter().any(|path| same_file(path, &include.path)) {
+                return Err(Error::IncludeCycle { chain: included });
+            }
+            *item = Item::List(include.parse_included(chain).map_err(|e| match e {
+                e @ Error::Include { .. } | e @ Error::IncludeCycle { .. } => e,
+                e => Error::Include {
+                    chain: included,
+                    source: Box::new(e),
+                },
+            })?);
         }
+        Ok(())
+    }
+}
+
+/// Paths point to the same existing file
+fn same_file(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
     }
 }
 
@@ -85,7 +856,7 @@ impl Script {
 mod tests {
     use std::path::PathBuf;
 
-    use crate::runitem::RunItem;
+    use serde_json::Value;
 
     use super::*;
 
@@ -94,22 +865,32 @@ mod tests {
         let wrong_script = Script::new(PathBuf::from("."));
 
         assert_eq!(
-            wrong_script.parse(),
-            Err("fail to read script with error: Is a directory (os error 21)".to_string())
+            wrong_script.parse().unwrap_err().to_string(),
+            "fail to read script . with error: Is a directory (os error 21)".to_string()
         );
 
         let wrong_script = Script::new(PathBuf::new());
 
         assert_eq!(
-            wrong_script.parse(),
-            Err("Script:  doesn't exist".to_string())
+            wrong_script.parse().unwrap_err().to_string(),
+            "Script:  doesn't exist".to_string()
         );
 
+        let wrong_script = Script::new(PathBuf::from("Cargo.toml")).with_format(Format::Yaml);
+
+        match wrong_script.parse().unwrap_err() {
+            Error::Parse { path, location, .. } => {
+                assert_eq!(path, Some(PathBuf::from("Cargo.toml")));
+                assert!(location.is_some());
+            }
+            e => panic!("unexpected error: {}", e),
+        }
+
         let right_script = Script::new(PathBuf::from("tests/test_script.yaml"));
 
         assert_eq!(
-            right_script.parse(),
-            Ok(List::Promiscuous(PromiscuousList::new(vec![])))
+            right_script.parse().unwrap(),
+            List::Promiscuous(PromiscuousList::new(vec![]))
         );
     }
 
@@ -119,7 +900,7 @@ mod tests {
 
         let encoded = serde_yaml::to_string(&item).unwrap();
 
-        assert_eq!(encoded, "---\nPromiscuous:\n  items: []".to_string());
+        assert_eq!(encoded, "---\nPromiscuous:\n  items: []\n".to_string());
 
         let decoded: List = serde_yaml::from_str(&encoded).unwrap();
 
@@ -142,7 +923,39 @@ mod tests {
              \n    - name: \"true\"\
              \n      args: []\
              \n    - name: \"true\"\
-             \n      args: []"
+             \n      args: []\n"
+                .to_string()
+        );
+
+        let decoded: List = serde_yaml::from_str(&encoded).unwrap();
+
+        assert_eq!(decoded, items);
+    }
+
+    #[test]
+    fn serde_defaults() {
+        let items = List::Silent(
+            SilentList::new(vec![RunItem::new("pwd".to_string(), vec![])
+                .with_env("B", "2")
+                .with_cwd("src")])
+            .with_defaults(Defaults::new().with_env("A", "1").with_env_clear(true)),
+        );
+
+        let encoded = serde_yaml::to_string(&items).unwrap();
+
+        assert_eq!(
+            encoded,
+            "---\nSilent:\
+             \n  items:\
+             \n    - name: pwd\
+             \n      args: []\
+             \n      env:\
+             \n        B: \"2\"\
+             \n      cwd: src\
+             \n  defaults:\
+             \n    env:\
+             \n      A: \"1\"\
+             \n    env_clear: true\n"
                 .to_string()
         );
 
@@ -151,12 +964,364 @@ mod tests {
         assert_eq!(decoded, items);
     }
 
+    #[test]
+    fn vars() {
+        let script = Script::new(PathBuf::from("tests/test_script_for_run_vars.yaml"))
+            .with_vars(Vars::new().with_var("MODE", "set"));
+
+        let error = script.parse().unwrap().run().status().unwrap_err();
+        assert!(matches!(error, Error::Item { index: 0, .. }));
+
+        let encoded = "vars:\n  A: a\nSilent:\n  items:\n    - name: \"${B}\"\n      args: []\n";
+        let decoded = match serde_yaml::from_str(encoded).unwrap() {
+            ScriptContent::List(script) => script,
+            ScriptContent::Targets(_) => panic!("expected script without targets"),
+        };
+        let mut list = decoded.list;
+
+        assert_eq!(
+            list.interpolate(&decoded.vars).unwrap_err().to_string(),
+            "undefined variable `B`"
+        );
+    }
+
+    #[test]
+    fn graph() {
+        let script = Script::new(PathBuf::from("tests/test_script_graph_cycle.yaml"));
+
+        assert_eq!(
+            script.parse().unwrap_err().to_string(),
+            "dependency cycle between items: test, lint"
+        );
+    }
+
+    #[test]
+    fn targets() {
+        let script = Script::new(PathBuf::from("tests/test_script_for_run_targets.yaml"));
+
+        let targets = script.targets().unwrap();
+        let names: Vec<&str> = targets.iter().map(|(name, _)| name).collect();
+        assert_eq!(names, vec!["build", "fail", "test"]);
+        assert_eq!(targets.default_target(), Some("test"));
+        assert_eq!(
+            targets.iter().next().unwrap().1.description(),
+            Some("Build everything")
+        );
+
+        let result = script.parse().unwrap().run().status();
+        assert!(result.expect("failed to execute process").success());
+
+        let script = script.with_target("fail");
+        assert!(script.parse().unwrap().run().status().is_err());
+
+        let script = script.with_target("deploy");
+        assert_eq!(
+            script.parse().unwrap_err().to_string(),
+            "unknown target `deploy`, available targets: build, fail, test"
+        );
+
+        let script = Script::new(PathBuf::from("tests/test_script.yaml")).with_target("build");
+        assert_eq!(
+            script.parse().unwrap_err().to_string(),
+            "unknown target `build`, script has no targets"
+        );
+        assert!(script.targets().unwrap().is_empty());
+    }
+
+    #[test]
+    fn cleanup() {
+        let script = Script::new(PathBuf::from("tests/test_script_for_run_cleanup.yaml"));
+        assert!(script.parse().unwrap().run().status().is_err());
+
+        let cleanup = script.cleanup().unwrap();
+        let plan = crate::plan::Plan::new(&cleanup);
+        let items: Vec<_> = plan.items().collect();
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].args(), ["cleaned", "=", "cleaned"]);
+        assert!(cleanup.run().status().unwrap().success());
+
+        let script = Script::new(PathBuf::from("tests/test_script_for_run_targets.yaml"));
+        let cleanup = script.clone().with_target("fail").cleanup().unwrap();
+        assert_eq!(crate::plan::Plan::new(&cleanup).items().count(), 1);
+        assert!(cleanup.run().status().unwrap().success());
+        let cleanup = script.cleanup().unwrap();
+        assert_eq!(crate::plan::Plan::new(&cleanup).items().count(), 0);
+
+        // Cleanup items get defaults of the run list like its own items
+        let script = Script::from_str(
+            "---\ncleanup:\n  - shell: \"true\"\nSilent:\n  defaults:\n    shell: [\"bash\", \"-c\"]\n    env: {X: \"1\"}\n  items:\n    - shell: \"true\"\n",
+        );
+        let cleanup = script.cleanup().unwrap();
+        let plan = crate::plan::Plan::new(&cleanup);
+        let items: Vec<_> = plan.items().collect();
+        assert_eq!(items[0].program(), "bash");
+        assert_eq!(items[0].env()["X"], "1");
+    }
+
+    #[test]
+    fn include() {
+        let script = Script::new(PathBuf::from("tests/test_script_for_run_include.yaml"));
+        let list = script.parse().unwrap();
+        assert_eq!(crate::plan::Plan::new(&list).items().count(), 3);
+
+        let script = Script::new(PathBuf::from("tests/include/cycle_a.yaml"));
+        assert_eq!(
+            script.parse().unwrap_err().to_string(),
+            "include cycle: tests/include/cycle_a.yaml -> tests/include/cycle_b.yaml -> \
+             tests/include/./cycle_a.yaml"
+        );
+
+        let script = Script::new(PathBuf::from("tests/include/broken.yaml"));
+        let error = script.parse().unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Script: tests/include/missing.yaml doesn't exist \
+             (include chain: tests/include/broken.yaml -> tests/include/missing.yaml)"
+        );
+        assert!(matches!(error, Error::Include { .. }));
+    }
+
+    #[test]
+    fn convert() {
+        let script = Script::new(PathBuf::from("tests/test_script_for_run_vars.yaml"));
+        let list = script.parse().unwrap();
+
+        let dir = std::env::temp_dir();
+        for format in [Format::Json, Format::Toml, Format::Yaml].iter() {
+            let path = dir.join(format!("automatic_convert.{}", format));
+            std::fs::write(&path, script.convert(*format).unwrap()).unwrap();
+            let converted = Script::new(path.clone()).parse();
+            std::fs::remove_file(&path).unwrap();
+            assert_eq!(converted.unwrap(), list, "{}", format);
+        }
+
+        let script = Script::new(PathBuf::from("tests/test_script_for_run_targets.yaml"))
+            .with_vars(Vars::new().with_var("BUILD_ARGS", "x"));
+        let encoded = script.convert(Format::Json).unwrap();
+        let path = dir.join("automatic_convert_targets.json");
+        std::fs::write(&path, encoded).unwrap();
+        let converted = Script::new(path.clone())
+            .with_vars(Vars::new().with_var("BUILD_ARGS", "x"))
+            .targets();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(converted.unwrap(), script.targets().unwrap());
+
+        let error = Script::new(PathBuf::from("tests/test_script_for_run_vars.yaml"))
+            .with_format(Format::Json)
+            .parse()
+            .unwrap_err();
+        assert!(matches!(error, Error::Parse { path: Some(_), .. }));
+    }
+
+    #[test]
+    fn inline() {
+        let content = "---\nvars:\n  NAME: inline\nSilent:\n  items:\n    - include: tests/test_script.yaml\n    - shell: \"echo ${NAME}\"\n";
+        let expected = List::Silent(SilentList::from_items(vec![
+            Item::List(List::Promiscuous(PromiscuousList::new(vec![]))),
+            Item::Run(RunItem::shell("echo inline".to_string())),
+        ]));
+
+        assert_eq!(Script::from_str(content).parse().unwrap(), expected);
+        assert_eq!(
+            Script::from_reader(content.as_bytes())
+                .unwrap()
+                .parse()
+                .unwrap(),
+            expected
+        );
+
+        let error = Script::from_str("Silent: [").parse().unwrap_err();
+        assert!(matches!(error, Error::Parse { path: Some(path), .. } if path == Path::new("-")));
+    }
+
+    #[test]
+    fn parse_error_location() {
+        let error = |content: &str| match Script::from_str(content).parse().unwrap_err() {
+            Error::Parse {
+                location, message, ..
+            } => (location, message),
+            e => panic!("unexpected error: {}", e),
+        };
+
+        let (location, message) = error(
+            "---\nSilent:\n  items:\n    - shell: \"true\"\n    - Parallel:\n        policy: fail_fast\n        items: []\n",
+        );
+        assert_eq!(location, Some((6, 17)));
+        assert!(message.starts_with(
+            "Silent.items[1].Parallel.policy: unknown variant `fail_fast`, expected `WaitAll` or `FailFast`"
+        ));
+
+        let (location, message) = error(
+            "---\ntargets:\n  build:\n    cleanup:\n      - name: \"true\"\n    Silent:\n      items: []\n",
+        );
+        assert_eq!(location, Some((5, 13)));
+        assert!(message.starts_with("targets.build.cleanup[0]: missing field `args`"));
+
+        let (location, message) = error("---\nvars: {}\nSilnt:\n  items: []\n");
+        assert_eq!(location, Some((3, 1)));
+        assert!(message.starts_with(
+            "unknown list `Silnt`, expected one of \
+             Interactive, Promiscuous, Silent, Parallel, Pipeline, Graph"
+        ));
+
+        let (location, message) = error("---\nSilent:\n  items: []\nGraph:\n  items: []\n");
+        assert_eq!(location, Some((4, 1)));
+        assert!(message.starts_with("more than one list: `Silent` and `Graph`"));
+
+        let (_, message) = error("---\nvars: {}\n");
+        assert!(message.starts_with("no list, expected one of Interactive"));
+    }
+
+    #[test]
+    fn schema() {
+        let schema = serde_json::to_value(Script::schema()).unwrap();
+
+        assert_eq!(schema["title"], "Script");
+        for name in [
+            "List",
+            "InteractiveList",
+            "SilentList",
+            "PromiscuousList",
+            "RunItem",
+        ]
+        .iter()
+        {
+            assert!(schema["definitions"][name].is_object(), "{}", name);
+        }
+        assert_eq!(schema["anyOf"][1]["required"][0], "targets");
+        assert_eq!(schema["definitions"]["SilentList"]["required"][0], "items");
+        assert!(schema["definitions"]["ScriptFile"]["oneOf"][0]
+            .get("additionalProperties")
+            .is_none());
+    }
+
+    /// Validate `value` against `schema` with keywords which script schema
+    /// uses, formats and number limits are not checked
+    fn validate(schema: &Value, root: &Value, value: &Value) -> bool {
+        let all = |key: &str| {
+            schema[key]
+                .as_array()
+                .map(|schemas| schemas.iter().filter(|s| validate(s, root, value)).count())
+        };
+        if let Some(name) = schema["$ref"].as_str() {
+            let name = name.trim_start_matches("#/definitions/");
+            return validate(&root["definitions"][name], root, value);
+        }
+        let type_of = match value {
+            Value::Null => "null",
+            Value::Bool(_) => "boolean",
+            Value::Number(n) if n.is_f64() => "number",
+            Value::Number(_) => "integer",
+            Value::String(_) => "string",
+            Value::Array(_) => "array",
+            Value::Object(_) => "object",
+        };
+        let types = match &schema["type"] {
+            Value::String(t) => vec![t.as_str()],
+            Value::Array(types) => types.iter().filter_map(Value::as_str).collect(),
+            _ => vec![type_of],
+        };
+        if !types
+            .iter()
+            .any(|t| *t == type_of || (*t == "number" && type_of == "integer"))
+        {
+            return false;
+        }
+        if let Some(values) = schema["enum"].as_array() {
+            if !values.contains(value) {
+                return false;
+            }
+        }
+        if let Value::Array(items) = value {
+            if !items.iter().all(|item| match schema.get("items") {
+                Some(items) => validate(items, root, item),
+                None => true,
+            }) {
+                return false;
+            }
+        }
+        if let Value::Object(fields) = value {
+            let required = schema["required"].as_array().cloned().unwrap_or_default();
+            if !required
+                .iter()
+                .all(|f| fields.contains_key(f.as_str().unwrap_or_default()))
+            {
+                return false;
+            }
+            for (name, field) in fields {
+                let valid = match (
+                    schema["properties"].get(name),
+                    schema.get("additionalProperties"),
+                ) {
+                    (Some(property), _) => validate(property, root, field),
+                    (None, Some(Value::Bool(allowed))) => *allowed,
+                    (None, Some(additional)) => validate(additional, root, field),
+                    (None, None) => true,
+                };
+                if !valid {
+                    return false;
+                }
+            }
+        }
+        all("anyOf") != Some(0)
+            && all("oneOf").unwrap_or(1) == 1
+            && !schema
+                .get("not")
+                .is_some_and(|not| validate(not, root, value))
+    }
+
+    #[test]
+    fn schema_validates_items() {
+        let schema = serde_json::to_value(Script::schema()).unwrap();
+        let valid = |source: &str| {
+            let value: Value = serde_yaml::from_str(source).unwrap();
+            validate(&schema, &schema, &value)
+        };
+
+        assert!(valid(
+            "Silent:\n  items:\n    - name: ls\n      args: [-l]\n"
+        ));
+        assert!(valid(
+            "Silent:\n  items:\n    - shell: ls\n      timeout: 1.5\n"
+        ));
+        assert!(valid("Silent:\n  items:\n    - include: other.yaml\n"));
+        assert!(valid(
+            "Silent:\n  items:\n    - Parallel:\n        items:\n          - shell: ls\n"
+        ));
+
+        assert!(!valid(
+            "Silent:\n  items:\n    - shell: ls\n      cdw: /tmp\n"
+        ));
+        assert!(!valid(
+            "Silent:\n  items:\n    - name: ls\n      args: []\n      shell: ls\n"
+        ));
+        assert!(!valid(
+            "Silent:\n  items:\n    - name: ls\n      shell: ls\n"
+        ));
+        assert!(!valid("Silent:\n  items:\n    - args: []\n"));
+        assert!(!valid(
+            "Silent:\n  items:\n    - include: other.yaml\n      shell: ls\n"
+        ));
+    }
+
     #[test]
     fn test_run_script() {
         let items = vec![
             "tests/test_script_for_run.yaml",
             "tests/test_script_for_run_silent.yaml",
             "tests/test_script_for_run_interactive.yaml",
+            "tests/test_script_for_run_env.yaml",
+            "tests/test_script_for_run_parallel.yaml",
+            "tests/test_script_for_run_nested.yaml",
+            "tests/test_script_for_run_pipeline.yaml",
+            "tests/test_script_for_run_vars.yaml",
+            "tests/test_script_for_run_shell.yaml",
+            "tests/test_script_for_run_ignore.yaml",
+            "tests/test_script_for_run_when.yaml",
+            "tests/test_script_for_run_graph.yaml",
+            "tests/test_script_for_run_include.yaml",
+            "tests/test_script_for_run_json.json",
+            "tests/test_script_for_run_toml.toml",
         ];
 
         for item in items {
@@ -0,0 +1,230 @@
+//! Graceful shutdown of run on SIGINT and SIGTERM
+//!
+//! Signal received by automatic is forwarded to running processes, they are
+//! killed if they still run after grace period, the rest of run list is not
+//! started. SIGINT is forwarded only to processes which run in their own
+//! process group, others share group of automatic and get SIGINT of terminal
+//! themselves. Second signal exits automatic immediately.
+//!
+//! # Example
+//!```
+//!use std::path::PathBuf;
+//!use std::time::Duration;
+//!
+//!use automatic::script::Script;
+//!use automatic::shutdown::Shutdown;
+//!
+//!let shutdown = Shutdown::install()
+//!    .unwrap()
+//!    .with_grace(Duration::from_secs(1));
+//!
+//!let list = Script::new(PathBuf::from("tests/test_script_for_run.yaml"))
+//!    .parse()
+//!    .unwrap();
+//!
+//!let result = shutdown.run(&list).status().expect("failed to execute process");
+//!assert!(result.success());
+//!assert_eq!(shutdown.exit_code(), None);
+//!```
+
+use std::io;
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use crate::item::{Context, RunIn};
+use crate::run::RunStatus;
+use crate::runner::{Cancel, DEFAULT_GRACE};
+use crate::script::List;
+
+/// Received signal, `0` if there is no one
+static SIGNAL: AtomicI32 = AtomicI32::new(0);
+
+/// Flag raised on received signal
+static CANCEL: OnceLock<Cancel> = OnceLock::new();
+
+/// Write end of pipe which signal handler wakes raising thread through, the
+/// handler can't raise cancel flag itself, `-1` until thread is started
+static WAKE: AtomicI32 = AtomicI32::new(-1);
+
+/// Signal received by installed handlers
+pub(crate) fn received() -> Option<i32> {
+    match SIGNAL.load(Ordering::SeqCst) {
+        0 => None,
+        signal => Some(signal),
+    }
+}
+
+#[cfg(unix)]
+extern "C" fn handle(signal: libc::c_int) {
+    if SIGNAL.swap(signal, Ordering::SeqCst) != 0 {
+        unsafe { libc::_exit(128 + signal) };
+    }
+    let wake = WAKE.load(Ordering::SeqCst);
+    if wake >= 0 {
+        unsafe { libc::write(wake, [0u8].as_ptr() as *const libc::c_void, 1) };
+    }
+}
+
+/// Start thread which raises `cancel` when signal handler wakes it
+#[cfg(unix)]
+fn start_raising(cancel: Cancel) -> io::Result<()> {
+    use std::fs::File;
+    use std::io::Read;
+    use std::os::unix::io::FromRawFd;
+    use std::thread;
+
+    let mut fds = [0; 2];
+    if unsafe { libc::pipe(fds.as_mut_ptr()) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // Items don't inherit pipe, handler doesn't block on full one
+    for fd in fds.iter() {
+        unsafe { libc::fcntl(*fd, libc::F_SETFD, libc::FD_CLOEXEC) };
+    }
+    unsafe { libc::fcntl(fds[1], libc::F_SETFL, libc::O_NONBLOCK) };
+
+    let mut wakes = unsafe { File::from_raw_fd(fds[0]) };
+    thread::spawn(move || {
+        let mut wake = [0; 1];
+        loop {
+            match wakes.read(&mut wake) {
+                Ok(0) => break,
+                Ok(_) => cancel.raise(),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => (),
+                Err(_) => break,
+            }
+        }
+    });
+    WAKE.store(fds[1], Ordering::SeqCst);
+    Ok(())
+}
+
+/// Handlers of SIGINT and SIGTERM which cancel run
+pub struct Shutdown {
+    cancel: Cancel,
+    grace: Duration,
+}
+
+impl Shutdown {
+    /// Install signal handlers, they are shared by every instance
+    pub fn install() -> io::Result<Self> {
+        let cancel = CANCEL.get_or_init(Cancel::new).clone();
+
+        #[cfg(unix)]
+        {
+            use std::sync::Mutex;
+
+            static STARTED: Mutex<bool> = Mutex::new(false);
+
+            let mut started = STARTED.lock().unwrap_or_else(|e| e.into_inner());
+            if !*started {
+                start_raising(cancel.clone())?;
+                *started = true;
+            }
+        }
+
+        #[cfg(unix)]
+        for signal in &[libc::SIGINT, libc::SIGTERM] {
+            let handler: extern "C" fn(libc::c_int) = handle;
+            unsafe {
+                let mut action: libc::sigaction = std::mem::zeroed();
+                action.sa_sigaction = handler as libc::sighandler_t;
+                action.sa_flags = libc::SA_RESTART;
+                libc::sigemptyset(&mut action.sa_mask);
+                if libc::sigaction(*signal, &action, std::ptr::null_mut()) != 0 {
+                    return Err(io::Error::last_os_error());
+                }
+            }
+        }
+
+        Ok(Self {
+            cancel,
+            grace: DEFAULT_GRACE,
+        })
+    }
+
+    /// Set time between forwarded signal and kill of running processes
+    pub fn with_grace(mut self, grace: Duration) -> Self {
+        self.grace = grace;
+        self
+    }
+
+    pub fn grace(&self) -> Duration {
+        self.grace
+    }
+
+    /// Flag which is raised by received signal
+    pub fn cancel(&self) -> Cancel {
+        self.cancel.clone()
+    }
+
+    /// Received signal, `None` if run wasn't interrupted
+    pub fn signal(&self) -> Option<i32> {
+        received()
+    }
+
+    /// Exit code of interrupted run: 130 for SIGINT, 143 for SIGTERM
+    pub fn exit_code(&self) -> Option<i32> {
+        self.signal().map(|signal| 128 + signal)
+    }
+
+    /// Run list which is cancelled by received signal
+    pub fn run(&self, list: &List) -> Box<dyn RunStatus> {
+        let ctx = Context {
+            grace: Some(self.grace),
+            ..Context::default()
+        };
+        list.run_in(&ctx.with_cancel(self.cancel()))
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::thread;
+    use std::time::Instant;
+
+    use crate::error::Error;
+    use crate::interactive_list::InteractiveList;
+    use crate::runitem::RunItem;
+
+    use super::*;
+
+    #[test]
+    fn forward_signal() {
+        let shutdown = Shutdown::install()
+            .unwrap()
+            .with_grace(Duration::from_secs(5));
+        let list = List::Interactive(InteractiveList::new(vec![
+            RunItem::new(
+                "sh".to_string(),
+                vec![
+                    "-c".to_string(),
+                    "trap '' TERM; trap 'exit 3' INT; while true; do sleep 0.05; done".to_string(),
+                ],
+            )
+            .with_process_group(true),
+            RunItem::new("true".to_string(), vec![]),
+        ]));
+
+        let start = Instant::now();
+        let interrupt = thread::spawn(|| {
+            thread::sleep(Duration::from_millis(300));
+            unsafe { libc::raise(libc::SIGINT) };
+        });
+        let status = shutdown.run(&list);
+        interrupt.join().unwrap();
+
+        // SIGINT is forwarded, item ignoring SIGTERM exits without kill
+        assert!(start.elapsed() < Duration::from_secs(4));
+        match status.status() {
+            Err(Error::Item { index: 0, source }) => {
+                assert!(matches!(*source, Error::Cancelled { .. }))
+            }
+            _ => panic!("expected cancelled item"),
+        }
+        assert_eq!(status.report().items.len(), 1);
+        assert_eq!(shutdown.signal(), Some(libc::SIGINT));
+        assert_eq!(shutdown.exit_code(), Some(130));
+    }
+}
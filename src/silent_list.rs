@@ -18,36 +18,69 @@
 //!assert!(result.success());
 //!```
 
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use crate::run::{ExitStatus, Run, RunStatus};
-use crate::runitem::RunItem;
+use crate::error::Error;
+use crate::item::{Context, Item, RunIn};
+use crate::report::Report;
+use crate::run::{check_exit, ExitStatus, Run, RunStatus};
+use crate::runitem::{Defaults, RunItem};
+use crate::vars::Vars;
 
-#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+#[derive(Debug, PartialEq, Serialize, Deserialize, JsonSchema, Clone)]
+#[serde(deny_unknown_fields)]
 pub struct SilentList {
-    items: Vec<RunItem>,
+    items: Vec<Item>,
+    #[serde(default, skip_serializing_if = "Defaults::is_empty")]
+    defaults: Defaults,
 }
 
 impl SilentList {
     pub fn new(items: Vec<RunItem>) -> Self {
-        Self { items }
-    }
-
-    fn run_internal(&self) -> Result<(), String> {
-        for item in self.items.iter() {
-            let result = match item.run().status() {
-                Err(e) => {
-                    return Err(e.to_string());
-                }
-                Ok(result) => result,
-            };
-
-            if !result.success() {
-                return Err(format!(
-                    "runned item return fail execution state with code: {}",
-                    result.code().unwrap()
-                ));
-            }
+        Self::from_items(items.into_iter().map(Item::from).collect())
+    }
+
+    pub fn from_items(items: Vec<Item>) -> Self {
+        Self {
+            items,
+            defaults: Defaults::new(),
+        }
+    }
+
+    pub fn with_defaults(mut self, defaults: Defaults) -> Self {
+        self.defaults = defaults;
+        self
+    }
+
+    pub fn items(&self) -> &[Item] {
+        &self.items
+    }
+
+    pub fn defaults(&self) -> &Defaults {
+        &self.defaults
+    }
+
+    pub(crate) fn items_mut(&mut self) -> &mut Vec<Item> {
+        &mut self.items
+    }
+
+    pub(crate) fn interpolate(&mut self, vars: &Vars) -> Result<(), Error> {
+        self.defaults.interpolate(vars)?;
+        for item in self.items.iter_mut() {
+            item.interpolate(vars)?;
+        }
+        Ok(())
+    }
+
+    fn run_internal(&self, ctx: &Context, report: &mut Report) -> Result<(), Error> {
+        for (index, item) in self.items.iter().enumerate() {
+            let status = item.run_in(ctx, |r| r);
+            report.extend(status.report());
+            check_exit(item, &*status).map_err(|e| Error::Item {
+                index,
+                source: Box::new(e),
+            })?;
         }
         Ok(())
     }
@@ -58,28 +91,42 @@ struct StatusHelper {}
 impl ExitStatus for StatusHelper {}
 
 struct RunItemStatus {
-    status: Result<(), String>,
+    status: Result<(), Error>,
+    report: Report,
 }
 
 impl RunStatus for RunItemStatus {
-    fn status(&self) -> Result<Box<dyn ExitStatus>, String> {
+    fn status(&self) -> Result<Box<dyn ExitStatus>, Error> {
         match &self.status {
             Ok(_) => Ok(Box::new(StatusHelper {})),
-            Err(e) => Err(e.to_string()),
+            Err(e) => Err(e.clone()),
         }
     }
+
+    fn report(&self) -> Report {
+        self.report.clone()
+    }
+}
+
+impl RunIn for SilentList {
+    fn run_in(&self, ctx: &Context) -> Box<dyn RunStatus> {
+        let mut report = Report::new();
+        let status = self.run_internal(&ctx.nested(&self.defaults), &mut report);
+        Box::new(RunItemStatus { status, report })
+    }
 }
 
 impl Run for SilentList {
     fn run(&self) -> Box<dyn RunStatus> {
-        Box::new(RunItemStatus {
-            status: self.run_internal(),
-        })
+        self.run_in(&Context::default())
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::time::Duration;
+
+    use crate::retry::Retry;
     use crate::runitem::RunItem;
 
     use super::*;
@@ -108,9 +155,11 @@ mod tests {
         ];
 
         let runner = SilentList::new(items);
+        let error = runner.run().status().unwrap_err();
+        assert!(matches!(error.root(), Error::Spawn { item, .. } if item == "bla bla"));
         assert_eq!(
-            runner.run().status(),
-            Err("No such file or directory (os error 2)".to_string())
+            error.to_string(),
+            "item #1: fail to run `bla bla`: No such file or directory (os error 2)"
         );
 
         let items = vec![
@@ -120,9 +169,85 @@ mod tests {
         ];
 
         let runner = SilentList::new(items);
+        let error = runner.run().status().unwrap_err();
+        assert!(matches!(error.root(), Error::NonZeroExit { code: 1, .. }));
+        assert_eq!(
+            error.to_string(),
+            "item #1: `false` return fail execution state with code: 1"
+        );
+    }
+
+    #[test]
+    fn report_run() {
+        let items = vec![
+            RunItem::new("echo".to_string(), vec!["out".to_string()]),
+            RunItem::new(
+                "sh".to_string(),
+                vec!["-c".to_string(), "echo err >&2; exit 2".to_string()],
+            ),
+            RunItem::new("true".to_string(), vec![]),
+        ];
+
+        let report = SilentList::new(items).run().report();
+        assert_eq!(report.items.len(), 2);
+
+        assert!(report.items[0].success());
+        assert_eq!(report.items[0].name, "echo");
+        assert_eq!(report.items[0].stdout, b"out\n");
+
+        assert!(!report.items[1].success());
+        assert_eq!(report.items[1].code, Some(2));
+        assert_eq!(report.items[1].stderr, b"err\n");
+        assert_eq!(report.failed().count(), 1);
+    }
+
+    #[test]
+    fn signaled_run() {
+        let items = vec![RunItem::new(
+            "sh".to_string(),
+            vec!["-c".to_string(), "kill -9 $$".to_string()],
+        )];
+
+        let runner = SilentList::new(items);
+        let status = runner.run();
+        let error = status.status().unwrap_err();
+        assert!(matches!(error.root(), Error::Signaled { signal: 9, .. }));
+        assert_eq!(
+            error.to_string(),
+            "item #0: `sh -c kill -9 $$` terminated by SIGKILL"
+        );
+        assert_eq!(status.report().items[0].signal, Some(9));
+    }
+
+    #[test]
+    fn retry_run() {
+        let items = vec![
+            RunItem::new("true".to_string(), vec![]),
+            RunItem::new("false".to_string(), vec![]).with_retry(Retry::new(2)),
+        ];
+
+        let runner = SilentList::new(items);
+        assert_eq!(
+            runner.run().status().unwrap_err().to_string(),
+            "item #1: `false` return fail execution state with code: 1 after 2 attempts: code 1, code 1"
+        );
+    }
+
+    #[test]
+    fn timeout_run() {
+        let items = vec![
+            RunItem::new("true".to_string(), vec![]),
+            RunItem::new("sleep".to_string(), vec!["10".to_string()]),
+            RunItem::new("true".to_string(), vec![]),
+        ];
+
+        let runner = SilentList::new(items)
+            .with_defaults(Defaults::new().with_timeout(Duration::from_millis(50)));
+        let error = runner.run().status().unwrap_err();
+        assert!(matches!(error.root(), Error::Timeout { .. }));
         assert_eq!(
-            runner.run().status(),
-            Err("runned item return fail execution state with code: 1".to_string())
+            error.to_string(),
+            "item #1: `sleep 10` timed out after 0.05s"
         );
     }
 }
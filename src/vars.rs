@@ -0,0 +1,188 @@
+//! Script variables and `${VAR}` / `${env:VAR}` interpolation into items
+//!
+//! `${NAME}` is replaced by value of script variable `NAME`, `${env:NAME}`
+//! by value of environment variable `NAME` of automatic process, `$${` is
+//! kept as literal `${`. Reference to undefined variable is an error.
+//!
+//! # Example
+//!```
+//!use automatic::vars::Vars;
+//!
+//!let vars = Vars::new().with_var("VERSION", "1.2");
+//!
+//!assert_eq!(vars.interpolate("release-${VERSION}").unwrap(), "release-1.2");
+//!assert_eq!(vars.interpolate("$${VERSION}").unwrap(), "${VERSION}");
+//!assert!(vars.interpolate("${RELEASE}").is_err());
+//!```
+
+use std::collections::BTreeMap;
+use std::env;
+use std::path::PathBuf;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::error::Error;
+
+const ENV_PREFIX: &str = "env:";
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, JsonSchema, Clone, Default)]
+#[serde(transparent)]
+pub struct Vars {
+    #[serde(deserialize_with = "deserialize_scalars")]
+    #[schemars(with = "BTreeMap<String, Scalar>")]
+    vars: BTreeMap<String, String>,
+}
+
+impl Vars {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_var<K: Into<String>, V: Into<String>>(mut self, key: K, val: V) -> Self {
+        self.set(key, val);
+        self
+    }
+
+    /// Define variable or override its value
+    pub fn set<K: Into<String>, V: Into<String>>(&mut self, key: K, val: V) {
+        self.vars.insert(key.into(), val.into());
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.vars.get(key).map(String::as_str)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.vars.is_empty()
+    }
+
+    /// Override variables with values of `other`
+    pub fn extend(&mut self, other: Vars) {
+        self.vars.extend(other.vars);
+    }
+
+    /// Substitute references to variables of script and environment in `value`
+    pub fn interpolate(&self, value: &str) -> Result<String, Error> {
+        let mut result = String::with_capacity(value.len());
+        let mut rest = value;
+        while let Some(start) = rest.find("${") {
+            if rest[..start].ends_with('$') {
+                result.push_str(&rest[..start - 1]);
+                result.push_str("${");
+                rest = &rest[start + 2..];
+                continue;
+            }
+            result.push_str(&rest[..start]);
+            let end = rest[start..].find('}').ok_or_else(|| Error::Parse {
+                path: None,
+                location: None,
+                message: format!("unterminated variable reference in `{}`", value),
+            })?;
+            let name = &rest[start + 2..start + end];
+            result.push_str(&self.lookup(name)?);
+            rest = &rest[start + end + 1..];
+        }
+        result.push_str(rest);
+        Ok(result)
+    }
+
+    pub(crate) fn interpolate_path(&self, path: &mut PathBuf) -> Result<(), Error> {
+        if let Some(value) = path.to_str() {
+            *path = PathBuf::from(self.interpolate(value)?);
+        }
+        Ok(())
+    }
+
+    pub(crate) fn interpolate_env(&self, env: &mut BTreeMap<String, String>) -> Result<(), Error> {
+        for val in env.values_mut() {
+            *val = self.interpolate(val)?;
+        }
+        Ok(())
+    }
+
+    fn lookup(&self, name: &str) -> Result<String, Error> {
+        let value = match name.strip_prefix(ENV_PREFIX) {
+            Some(key) => env::var(key).ok(),
+            None => self.get(name).map(str::to_string),
+        };
+        value.ok_or_else(|| Error::UndefinedVariable {
+            name: name.to_string(),
+        })
+    }
+}
+
+/// Scalar value of variable, numbers and booleans are kept as strings
+#[derive(Deserialize, JsonSchema)]
+#[serde(untagged)]
+enum Scalar {
+    String(String),
+    Integer(i64),
+    Float(f64),
+    Bool(bool),
+}
+
+fn deserialize_scalars<'de, D>(deserializer: D) -> Result<BTreeMap<String, String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let vars = BTreeMap::<String, Scalar>::deserialize(deserializer)?;
+    Ok(vars
+        .into_iter()
+        .map(|(key, val)| {
+            let val = match val {
+                Scalar::String(s) => s,
+                Scalar::Integer(i) => i.to_string(),
+                Scalar::Float(f) => f.to_string(),
+                Scalar::Bool(b) => b.to_string(),
+            };
+            (key, val)
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interpolate() {
+        let vars = Vars::new().with_var("A", "1").with_var("DIR", "/opt/${A}");
+
+        assert_eq!(vars.interpolate("${A}${A}-x").unwrap(), "11-x");
+        assert_eq!(vars.interpolate("${DIR}").unwrap(), "/opt/${A}");
+        assert!(vars.interpolate("$A $$ ${").is_err());
+        assert_eq!(vars.interpolate("$A $$").unwrap(), "$A $$");
+
+        env::set_var("AUTOMATIC_VARS_TEST", "env");
+        assert_eq!(
+            vars.interpolate("${env:AUTOMATIC_VARS_TEST}").unwrap(),
+            "env"
+        );
+
+        assert_eq!(
+            vars.interpolate("${B}").unwrap_err().to_string(),
+            "undefined variable `B`"
+        );
+        assert_eq!(
+            vars.interpolate("${env:AUTOMATIC_VARS_UNDEFINED}")
+                .unwrap_err()
+                .to_string(),
+            "undefined variable `env:AUTOMATIC_VARS_UNDEFINED`"
+        );
+    }
+
+    #[test]
+    fn serde_vars() {
+        let vars: Vars = serde_yaml::from_str("A: a\nB: 1\nC: 1.5\nD: true\n").unwrap();
+
+        assert_eq!(
+            vars,
+            Vars::new()
+                .with_var("A", "a")
+                .with_var("B", "1")
+                .with_var("C", "1.5")
+                .with_var("D", "true")
+        );
+    }
+}
@@ -0,0 +1,72 @@
+use std::process::{Command, Output};
+
+/// Run `automatic` binary with `args`
+fn automatic(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_automatic"))
+        .args(args)
+        .output()
+        .expect("failed to execute automatic")
+}
+
+#[test]
+fn json_report_on_stdout() {
+    let script = "---
+Promiscuous:
+  items:
+    - shell: \"echo failed output; exit 3\"
+    - shell: \"echo skipped output\"
+      when:
+        env: AUTOMATIC_NO_SUCH_VAR
+    - Interactive:
+        items:
+          - shell: \"echo inherited output\"
+";
+    let output = automatic(&["-e", script, "--report", "json"]);
+
+    let report: serde_json::Value =
+        serde_json::from_slice(&output.stdout).expect("stdout is not json report");
+    assert_eq!(report["success"], false);
+    assert_eq!(report["items"].as_array().unwrap().len(), 3);
+    assert_eq!(report["items"][0]["code"], 3);
+    assert_eq!(report["items"][1]["skipped"], true);
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("failed output"));
+    assert!(stderr.contains("Skipped `echo skipped output`"));
+    assert!(stderr.contains("inherited output"));
+}
+
+#[test]
+fn failed_run_never_exits_zero() {
+    for list in &["Silent", "Parallel"] {
+        let script = format!(
+            "---\n{}:\n  items:\n    - name: \"true\"\n      args: []\n      success_codes: [3]\n",
+            list
+        );
+        let output = automatic(&["-e", &script]);
+        assert_eq!(output.status.code(), Some(1), "{} list", list);
+    }
+}
+
+#[test]
+fn fail_fast_exits_with_code_of_failure() {
+    let script = "---
+Parallel:
+  policy: FailFast
+  max_jobs: 2
+  items:
+    - name: sleep
+      args: [\"10\"]
+    - shell: \"sleep 0.2; exit 3\"
+";
+    let output = automatic(&["-e", script]);
+    assert_eq!(output.status.code(), Some(3));
+}
+
+#[test]
+fn wrong_grace_is_usage_error() {
+    for grace in &["-1", "nan", "1e30", "x"] {
+        let output = automatic(&["-e", "{Silent: {items: []}}", "--grace", grace]);
+        assert_eq!(output.status.code(), Some(64), "grace {}", grace);
+    }
+}